@@ -0,0 +1,2410 @@
+//! Encrypted credential vault shared by the pine frontends. It holds the credential model, the vault
+//! format and its storage, and does not depend on any GUI toolkit.
+
+use std::{borrow::Borrow, collections::{HashMap, HashSet}, fmt, fs, io::{self, Write}, ops::RangeInclusive, path, sync::{atomic::{self, AtomicU64}, Arc, Mutex, MutexGuard, OnceLock, PoisonError}, time};
+use aes::{cipher::{generic_array::GenericArray, BlockDecrypt, KeyInit}, Aes128};
+use aes_gcm::{aead::{Aead, Payload}, Aes256Gcm, Nonce};
+use rand::{seq::SliceRandom, Rng, RngCore};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use zeroize::{Zeroize, Zeroizing};
+
+const TITLE: &str = "pine";
+const FILE_NAME: &str = "localstorage.aes";
+const USAGE_FILE_NAME: &str = "usage";
+const MAGIC: &[u8] = b"PINE";
+const FORMAT_VERSION: u8 = 8;
+/// Custom fields without TOTP keys.
+const ID_FORMAT_VERSION: u8 = 7;
+/// Records without ids.
+const BOOKKEEPING_FORMAT_VERSION: u8 = 6;
+/// Records without folder and timestamps.
+const DETAILS_FORMAT_VERSION: u8 = 5;
+/// Records without title, urls, tags and custom fields.
+const PLAIN_RECORD_FORMAT_VERSION: u8 = 4;
+/// Generator policies without passphrase options.
+const CHARACTER_POLICY_FORMAT_VERSION: u8 = 3;
+/// First binary format, before records carried a generator policy.
+const BINARY_FORMAT_VERSION: u8 = 2;
+/// First versioned format, which still stored records as comma separated lines.
+const CSV_FORMAT_VERSION: u8 = 1;
+const CIPHER_AES_256_GCM: u8 = 1;
+const KEY_CHECK_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const SALT_LEN: usize = 32;
+const SECRET_PASSWORD: u8 = 0;
+const SECRET_PIN: u8 = 1;
+const SECRET_PASSPHRASE: u8 = 2;
+const FIELD_TEXT: u8 = 0;
+const FIELD_HIDDEN: u8 = 1;
+const FIELD_URL: u8 = 2;
+const FIELD_EMAIL: u8 = 3;
+const FIELD_TOTP: u8 = 4;
+const GENERATED_PIN_LEN: u32 = 6;
+/// The EFF large word list for passphrases, 7776 words of about 12.9 bits each. Every line carries
+/// the dice roll of its word in front of it, which is ignored. Published by the Electronic Frontier
+/// Foundation under CC BY 3.0, see https://www.eff.org/dice.
+const WORDLIST: &str = include_str!("../wordlists/eff_large_wordlist.txt");
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SecretKind {
+    Password,
+    Passphrase,
+    Pin,
+}
+
+impl fmt::Display for SecretKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Password => "Password",
+            Self::Passphrase => "Passphrase",
+            Self::Pin => "Pin",
+        }.fmt(f)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Secret {
+    Password(String),
+    Passphrase(String),
+    Pin(String),
+}
+
+impl Secret {
+    /// Parses the `kind:secret` notation of legacy vaults.
+    fn parse(value: &str) -> Option<Self> {
+        let (kind, secret) = value.split_once(':')?;
+        match kind {
+            "password" => Some(Self::Password(secret.to_owned())),
+            "pin" => Some(Self::Pin(secret.to_owned())),
+            _ => None,
+        }
+    }
+
+    pub fn new(kind: SecretKind, value: String) -> Self {
+        match kind {
+            SecretKind::Password => Self::Password(value),
+            SecretKind::Passphrase => Self::Passphrase(value),
+            SecretKind::Pin => Self::Pin(value),
+        }
+    }
+
+    /// Random secret drawn from the operating system CSPRNG, or `None` if `policy` allows no secret of `kind`.
+    /// Pins are not affected by the policy and never start with a zero, as the frontends keep them as numbers.
+    pub fn generate(kind: SecretKind, policy: &GeneratorPolicy) -> Option<Self> {
+        match kind {
+            SecretKind::Password => policy.generate().map(Self::Password),
+            SecretKind::Passphrase => policy.passphrase().map(Self::Passphrase),
+            SecretKind::Pin => Some(Self::Pin(rand::rngs::OsRng.gen_range(10u32.pow(GENERATED_PIN_LEN - 1)..10u32.pow(GENERATED_PIN_LEN)).to_string())),
+        }
+    }
+
+    /// Secrets may not be empty, and pins are numbers without leading zeros, as the frontends keep
+    /// them as numbers.
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Self::Pin(value) => value.parse::<u32>().is_ok_and(|pin| pin.to_string() == *value),
+            _ => !self.value().is_empty(),
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        match self {
+            Self::Password(value) => value,
+            Self::Passphrase(value) => value,
+            Self::Pin(value) => value,
+        }
+    }
+
+    pub fn kind(&self) -> SecretKind {
+        match self {
+            Self::Password(_) => SecretKind::Password,
+            Self::Passphrase(_) => SecretKind::Passphrase,
+            Self::Pin(_) => SecretKind::Pin,
+        }
+    }
+}
+
+/// Copies are wiped as well, but a `String` that grows leaves its old buffer behind unwiped.
+impl Drop for Secret {
+    fn drop(&mut self) {
+        match self {
+            Self::Password(value) | Self::Passphrase(value) | Self::Pin(value) => value.zeroize(),
+        }
+    }
+}
+
+/// Rules for generated passwords and passphrases. A credential keeps the policy its secret was
+/// generated with, so regenerating it satisfies the same site rules.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeneratorPolicy {
+    pub length: usize,
+    pub uppercase: bool,
+    pub lowercase: bool,
+    pub digits: bool,
+    pub symbols: bool,
+    /// Leaves out characters that are easily confused with each other, like `l`, `1` and `I`.
+    pub exclude_ambiguous: bool,
+    /// Includes at least one character of every selected class.
+    pub require_each: bool,
+    pub symbol_set: String,
+    /// Number of words in a passphrase.
+    pub words: usize,
+    pub separator: String,
+    /// Starts every word of a passphrase with a capital letter.
+    pub capitalize: bool,
+    /// Ends a passphrase with a random digit.
+    pub append_digit: bool,
+}
+
+impl Default for GeneratorPolicy {
+    fn default() -> Self {
+        Self {
+            length: 20,
+            uppercase: true,
+            lowercase: true,
+            digits: true,
+            symbols: true,
+            exclude_ambiguous: false,
+            require_each: true,
+            symbol_set: Self::SYMBOLS.to_string(),
+            words: 6,
+            separator: "-".to_string(),
+            capitalize: false,
+            append_digit: false,
+        }
+    }
+}
+
+impl GeneratorPolicy {
+    pub const MAX_LENGTH: usize = 256;
+    pub const MAX_WORDS: usize = 32;
+    const SYMBOLS: &'static str = "!#$%&()*+-./:;<=>?@[]^_{|}~";
+    const AMBIGUOUS: &'static str = "0O1lI|";
+    const UPPERCASE: u8 = 1;
+    const LOWERCASE: u8 = 2;
+    const DIGITS: u8 = 4;
+    const SYMBOL: u8 = 8;
+    const EXCLUDE_AMBIGUOUS: u8 = 16;
+    const REQUIRE_EACH: u8 = 32;
+    const CAPITALIZE: u8 = 1;
+    const APPEND_DIGIT: u8 = 2;
+
+    fn wordlist() -> &'static [&'static str] {
+        static WORDS: OnceLock<Vec<&str>> = OnceLock::new();
+        WORDS.get_or_init(|| WORDLIST.lines().filter_map(|line| line.split_whitespace().last()).collect())
+    }
+
+    /// Characters of every selected class. Whitespace and repeated symbols are ignored.
+    fn classes(&self) -> Vec<Vec<char>> {
+        let symbols = self.symbol_set.chars().filter(|c| !c.is_whitespace() && !c.is_control()).fold(String::new(), |mut symbols, c| {
+            if !symbols.contains(c) {
+                symbols.push(c);
+            }
+            symbols
+        });
+        [
+            (self.uppercase, ('A'..='Z').collect::<String>()),
+            (self.lowercase, ('a'..='z').collect()),
+            (self.digits, ('0'..='9').collect()),
+            (self.symbols, symbols),
+        ].into_iter().filter(|(selected, _)| *selected).map(|(_, class)| {
+            class.chars().filter(|c| !self.exclude_ambiguous || !Self::AMBIGUOUS.contains(*c)).collect()
+        }).collect()
+    }
+
+    /// Whether the policy allows any secret of `kind`.
+    pub fn is_valid(&self, kind: SecretKind) -> bool {
+        match kind {
+            SecretKind::Password => {
+                let classes = self.classes();
+                !classes.is_empty()
+                    && classes.iter().all(|class| !class.is_empty())
+                    && (1..=Self::MAX_LENGTH).contains(&self.length)
+                    && (!self.require_each || self.length >= classes.len())
+            },
+            SecretKind::Passphrase => (1..=Self::MAX_WORDS).contains(&self.words),
+            SecretKind::Pin => true,
+        }
+    }
+
+    /// Estimated entropy in bits of a secret of `kind` generated with this policy. Requiring every
+    /// class slightly lowers the actual entropy of passwords, which is not accounted for.
+    pub fn entropy(&self, kind: SecretKind) -> f64 {
+        if !self.is_valid(kind) {
+            return 0f64;
+        }
+        match kind {
+            SecretKind::Password => self.length as f64 * (self.classes().concat().len() as f64).log2(),
+            SecretKind::Passphrase => self.words as f64 * (Self::wordlist().len() as f64).log2() + if self.append_digit { 10f64.log2() } else { 0f64 },
+            SecretKind::Pin => (9f64 * 10f64.powi(GENERATED_PIN_LEN as i32 - 1)).log2(),
+        }
+    }
+
+    fn passphrase(&self) -> Option<String> {
+        if !self.is_valid(SecretKind::Passphrase) {
+            return None;
+        }
+        let mut rng = rand::rngs::OsRng;
+        let words = (0..self.words).filter_map(|_| Self::wordlist().choose(&mut rng)).map(|word| {
+            let mut chars = word.chars();
+            match (self.capitalize, chars.next()) {
+                (true, Some(first)) => first.to_uppercase().chain(chars).collect(),
+                _ => word.to_string(),
+            }
+        }).collect::<Vec<String>>();
+        let mut passphrase = words.join(&self.separator);
+        if self.append_digit {
+            passphrase.push(char::from(b'0' + rng.gen_range(0..10)));
+        }
+        Some(passphrase)
+    }
+
+    fn generate(&self) -> Option<String> {
+        if !self.is_valid(SecretKind::Password) {
+            return None;
+        }
+        let mut rng = rand::rngs::OsRng;
+        let classes = self.classes();
+        let pool = classes.concat();
+        let mut password = match self.require_each {
+            true => classes.iter().filter_map(|class| class.choose(&mut rng).copied()).collect(),
+            false => Vec::new(),
+        };
+        while password.len() < self.length {
+            password.extend(pool.choose(&mut rng));
+        }
+        password.shuffle(&mut rng);
+        Some(password.into_iter().collect())
+    }
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        let flags = [
+            (self.uppercase, Self::UPPERCASE),
+            (self.lowercase, Self::LOWERCASE),
+            (self.digits, Self::DIGITS),
+            (self.symbols, Self::SYMBOL),
+            (self.exclude_ambiguous, Self::EXCLUDE_AMBIGUOUS),
+            (self.require_each, Self::REQUIRE_EACH),
+        ].into_iter().filter(|(set, _)| *set).fold(0, |flags, (_, flag)| flags | flag);
+        buffer.extend_from_slice(&(self.length as u32).to_le_bytes());
+        buffer.push(flags);
+        write_str(buffer, &self.symbol_set);
+        let flags = [(self.capitalize, Self::CAPITALIZE), (self.append_digit, Self::APPEND_DIGIT)].into_iter().filter(|(set, _)| *set).fold(0, |flags, (_, flag)| flags | flag);
+        buffer.extend_from_slice(&(self.words as u32).to_le_bytes());
+        buffer.push(flags);
+        write_str(buffer, &self.separator);
+    }
+
+    /// Policies of version 3 vaults end with the symbol set and get the default passphrase options.
+    fn decode(reader: &mut Reader, version: u8) -> Result<Self, StoreError> {
+        let length = reader.u32()? as usize;
+        let flags = reader.u8()?;
+        let symbol_set = reader.string()?;
+        let default = Self::default();
+        let (words, passphrase_flags, separator) = match version {
+            CHARACTER_POLICY_FORMAT_VERSION => (default.words, 0, default.separator),
+            _ => (reader.u32()? as usize, reader.u8()?, reader.string()?),
+        };
+        Ok(Self {
+            length,
+            uppercase: flags & Self::UPPERCASE != 0,
+            lowercase: flags & Self::LOWERCASE != 0,
+            digits: flags & Self::DIGITS != 0,
+            symbols: flags & Self::SYMBOL != 0,
+            exclude_ambiguous: flags & Self::EXCLUDE_AMBIGUOUS != 0,
+            require_each: flags & Self::REQUIRE_EACH != 0,
+            symbol_set,
+            words,
+            separator,
+            capitalize: passphrase_flags & Self::CAPITALIZE != 0,
+            append_digit: passphrase_flags & Self::APPEND_DIGIT != 0,
+        })
+    }
+}
+
+/// Weakness found while estimating the strength of a secret.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StrengthHint {
+    Short,
+    CommonPassword,
+    KeyboardPattern,
+    Sequence,
+    Repeated,
+    Year,
+}
+
+impl fmt::Display for StrengthHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Short => "short",
+            Self::CommonPassword => "common password",
+            Self::KeyboardPattern => "keyboard pattern",
+            Self::Sequence => "sequence",
+            Self::Repeated => "repeated characters",
+            Self::Year => "year",
+        }.fmt(f)
+    }
+}
+
+/// Estimate of how hard a secret is to guess, in the spirit of zxcvbn. Common passwords, keyboard
+/// walks, sequences, repeats and years are charged as the few guesses they take, every other
+/// character as a pick from the character classes the secret uses.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Strength {
+    /// From 0, trivially guessable, to [`Strength::MAX_SCORE`].
+    pub score: u8,
+    pub entropy: f64,
+    pub hints: Vec<StrengthHint>,
+}
+
+impl Strength {
+    pub const MAX_SCORE: u8 = 4;
+    const MIN_LENGTH: usize = 8;
+    const MIN_PATTERN_LENGTH: usize = 3;
+    /// Entropy in bits needed for each score above 0.
+    const SCORE_ENTROPY: [f64; 4] = [28f64, 36f64, 60f64, 80f64];
+    const KEYBOARD_ROWS: [&'static str; 5] = ["`1234567890-=", "qwertyuiop[]\\", "asdfghjkl;'", "zxcvbnm,./", "~!@#$%^&*()_+"];
+    /// Most used passwords, most common first.
+    const COMMON_PASSWORDS: [&'static str; 60] = [
+        "123456", "password", "123456789", "12345678", "12345", "qwerty", "1234567", "111111", "123123", "abc123",
+        "1234567890", "000000", "iloveyou", "1234", "qwertyuiop", "654321", "monkey", "dragon", "123321", "letmein",
+        "football", "baseball", "sunshine", "princess", "welcome", "admin", "master", "shadow", "superman", "michael",
+        "trustno1", "batman", "starwars", "whatever", "freedom", "passw0rd", "login", "hello", "charlie", "azerty",
+        "qwerty123", "zaq1zaq1", "qazwsx", "mustang", "access", "flower", "secret", "computer", "internet", "pokemon",
+        "cheese", "soccer", "hockey", "killer", "jordan", "hunter", "ranger", "buster", "changeme", "default",
+    ];
+
+    /// The copies of the secret taken along the way are wiped once they are no longer needed.
+    pub fn estimate(secret: &str) -> Self {
+        let chars = Zeroizing::new(secret.chars().collect::<Vec<char>>());
+        let bits_per_char = (Self::pool_size(&chars) as f64).log2();
+        let mut hints = Vec::new();
+        let entropy = match Self::common_password(secret) {
+            Some((rank, suffix)) => {
+                hints.push(StrengthHint::CommonPassword);
+                ((rank + 1) as f64).log2() + Self::pattern_entropy(&chars[chars.len() - suffix..], bits_per_char, &mut hints)
+            },
+            None => Self::pattern_entropy(&chars, bits_per_char, &mut hints),
+        };
+        if !chars.is_empty() && chars.len() < Self::MIN_LENGTH {
+            hints.insert(0, StrengthHint::Short);
+        }
+        let score = match hints.contains(&StrengthHint::CommonPassword) {
+            true => 0,
+            false => Self::SCORE_ENTROPY.iter().filter(|threshold| entropy >= **threshold).count() as u8,
+        };
+        Self { score, entropy, hints }
+    }
+
+    /// Size of the character classes the secret draws from.
+    fn pool_size(chars: &[char]) -> usize {
+        let uses = |is_class: fn(&char) -> bool| chars.iter().any(is_class);
+        [
+            (uses(char::is_ascii_lowercase), 26),
+            (uses(char::is_ascii_uppercase), 26),
+            (uses(char::is_ascii_digit), 10),
+            (uses(|c| c.is_ascii() && !c.is_ascii_alphanumeric()), 33),
+            (uses(|c| !c.is_ascii()), 100),
+        ].into_iter().filter(|(used, _)| *used).map(|(_, size)| size).sum()
+    }
+
+    /// Rank of the common password the secret is made of, allowing for leetspeak and trailing digits
+    /// or symbols, and the length of that suffix.
+    fn common_password(secret: &str) -> Option<(usize, usize)> {
+        let lowercase = Zeroizing::new(secret.to_lowercase());
+        let base = lowercase.trim_end_matches(|c: char| c.is_ascii_digit() || c.is_ascii_punctuation());
+        let unleet = Zeroizing::new(base.chars().map(|c| match c {
+            '@' | '4' => 'a',
+            '3' => 'e',
+            '1' | '!' => 'i',
+            '0' => 'o',
+            '$' | '5' => 's',
+            '7' | '+' => 't',
+            c => c,
+        }).collect::<String>());
+        let rank = |candidate: &str| Self::COMMON_PASSWORDS.iter().position(|common| *common == candidate);
+        match rank(&lowercase) {
+            Some(rank) => Some((rank, 0)),
+            None if !base.is_empty() => rank(base).or_else(|| rank(&unleet)).map(|rank| (rank, lowercase[base.len()..].chars().count())),
+            None => None,
+        }
+    }
+
+    fn pattern_entropy(chars: &[char], bits_per_char: f64, hints: &mut Vec<StrengthHint>) -> f64 {
+        let lowercase = Zeroizing::new(chars.iter().map(char::to_ascii_lowercase).collect::<Vec<char>>());
+        let mut entropy = 0f64;
+        let mut i = 0;
+        while i < lowercase.len() {
+            let rest = &lowercase[i..];
+            let run = |is_next: &dyn Fn(char, char) -> bool| 1 + rest.windows(2).take_while(|pair| is_next(pair[0], pair[1])).count();
+            let year = rest.get(..4).and_then(|digits| digits.iter().try_fold(0, |year, c| c.to_digit(10).map(|digit| year * 10 + digit))).filter(|year| (1900..=2099).contains(year));
+            let patterns = [
+                (StrengthHint::Repeated, run(&|a, b| a == b), 0f64),
+                (StrengthHint::Sequence, run(&|a, b| a.is_ascii_alphanumeric() && b as u32 == a as u32 + 1).max(run(&|a, b| a.is_ascii_alphanumeric() && a as u32 == b as u32 + 1)), 1f64),
+                (StrengthHint::KeyboardPattern, run(&|a, b| Self::KEYBOARD_ROWS.iter().any(|row| row.chars().zip(row.chars().skip(1)).any(|keys| keys == (a, b) || keys == (b, a)))), 1f64),
+            ];
+            let pattern = patterns.into_iter().filter(|(_, len, _)| *len >= Self::MIN_PATTERN_LENGTH).max_by_key(|(_, len, _)| *len);
+            match (pattern, year) {
+                (Some((hint, len, direction)), _) if len >= 4 || year.is_none() => {
+                    if !hints.contains(&hint) {
+                        hints.push(hint);
+                    }
+                    entropy += bits_per_char + (len as f64).log2() + direction;
+                    i += len;
+                },
+                (_, Some(_)) => {
+                    if !hints.contains(&StrengthHint::Year) {
+                        hints.push(StrengthHint::Year);
+                    }
+                    entropy += 200f64.log2();
+                    i += 4;
+                },
+                _ => {
+                    entropy += bits_per_char;
+                    i += 1;
+                },
+            }
+        }
+        entropy
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FieldKind {
+    #[default]
+    Text,
+    /// Masked like the secret until the credential is revealed.
+    Hidden,
+    Url,
+    Email,
+    /// Key of a time-based one-time password, see `Totp`.
+    Totp,
+}
+
+impl FieldKind {
+    pub const ALL: [Self; 5] = [Self::Text, Self::Hidden, Self::Url, Self::Email, Self::Totp];
+}
+
+impl fmt::Display for FieldKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text => "Text",
+            Self::Hidden => "Hidden",
+            Self::Url => "URL",
+            Self::Email => "Email",
+            Self::Totp => "TOTP",
+        }.fmt(f)
+    }
+}
+
+/// User defined, named value stored along with a credential.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CustomField {
+    pub name: String,
+    pub kind: FieldKind,
+    pub value: String,
+}
+
+impl Drop for CustomField {
+    fn drop(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TotpAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+/// Time-based one-time password generator of RFC 6238, set up from the `otpauth://totp/` URI behind
+/// the QR code of a site, or from a bare base32 key with the usual SHA1, 6 digits and 30 seconds.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Totp {
+    key: Zeroizing<Vec<u8>>,
+    pub algorithm: TotpAlgorithm,
+    pub digits: u32,
+    pub period: u64,
+}
+
+impl fmt::Debug for Totp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Totp").field("algorithm", &self.algorithm).field("digits", &self.digits).field("period", &self.period).finish_non_exhaustive()
+    }
+}
+
+impl Totp {
+    const URI_PREFIX: &'static str = "otpauth://totp/";
+
+    /// Labels and issuers of URIs are ignored, and so are parameters other than the key, algorithm,
+    /// digits and period.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let mut totp = Self { key: Zeroizing::default(), algorithm: TotpAlgorithm::default(), digits: 6, period: 30 };
+        match value.get(..Self::URI_PREFIX.len()).filter(|prefix| prefix.eq_ignore_ascii_case(Self::URI_PREFIX)) {
+            Some(_) => {
+                let (_, query) = value.split_once('?')?;
+                for (name, value) in query.split('&').filter_map(|parameter| parameter.split_once('=')) {
+                    match name.to_ascii_lowercase().as_str() {
+                        "secret" => totp.key = decode_base32(value)?,
+                        "algorithm" => totp.algorithm = match value.to_ascii_uppercase().as_str() {
+                            "SHA1" => TotpAlgorithm::Sha1,
+                            "SHA256" => TotpAlgorithm::Sha256,
+                            "SHA512" => TotpAlgorithm::Sha512,
+                            _ => return None,
+                        },
+                        "digits" => totp.digits = value.parse().ok().filter(|digits| (6..=8).contains(digits))?,
+                        "period" => totp.period = value.parse().ok().filter(|period| *period > 0)?,
+                        _ => (),
+                    }
+                }
+            },
+            None => totp.key = decode_base32(value)?,
+        }
+        (!totp.key.is_empty()).then_some(totp)
+    }
+
+    /// Code for the time step `unix_seconds` falls into, the dynamically truncated HMAC of RFC 4226.
+    pub fn code(&self, unix_seconds: u64) -> Option<String> {
+        let counter = (unix_seconds / self.period).to_be_bytes();
+        let digest = match self.algorithm {
+            TotpAlgorithm::Sha1 => mac::<Hmac<Sha1>>(&self.key, &counter)?,
+            TotpAlgorithm::Sha256 => mac::<Hmac<Sha256>>(&self.key, &counter)?,
+            TotpAlgorithm::Sha512 => mac::<Hmac<Sha512>>(&self.key, &counter)?,
+        };
+        let offset = (digest.last()? & 0x0f) as usize;
+        let truncated = u32::from_be_bytes(digest.get(offset..offset + 4)?.try_into().ok()?) & 0x7fff_ffff;
+        Some(format!("{:01$}", truncated % 10u32.pow(self.digits), self.digits as usize))
+    }
+
+    pub fn seconds_left(&self, unix_seconds: u64) -> u64 {
+        self.period - unix_seconds % self.period
+    }
+}
+
+fn mac<M: Mac + KeyInit>(key: &[u8], message: &[u8]) -> Option<Vec<u8>> {
+    let mut mac = <M as Mac>::new_from_slice(key).ok()?;
+    mac.update(message);
+    Some(mac.finalize().into_bytes().to_vec())
+}
+
+/// Base32 of RFC 4648 as TOTP keys are written, in either case and with padding, spaces or dashes.
+fn decode_base32(value: &str) -> Option<Zeroizing<Vec<u8>>> {
+    let mut decoded = Zeroizing::new(Vec::with_capacity(value.len() * 5 / 8));
+    let (mut bits, mut count) = (0u32, 0);
+    for c in value.chars().filter(|c| !matches!(c, '=' | ' ' | '-')) {
+        let digit = match c.to_ascii_uppercase() {
+            c @ 'A'..='Z' => c as u32 - 'A' as u32,
+            c @ '2'..='7' => c as u32 - '2' as u32 + 26,
+            _ => return None,
+        };
+        bits = bits << 5 | digit;
+        count += 5;
+        if count >= 8 {
+            count -= 8;
+            decoded.push((bits >> count) as u8);
+            bits &= (1 << count) - 1;
+        }
+    }
+    Some(decoded)
+}
+
+/// Random version 4 UUID that identifies a credential for as long as it exists. Credentials of vaults
+/// without ids get new ones when they are read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CredentialId([u8; 16]);
+
+impl CredentialId {
+    pub fn random() -> Self {
+        let mut bytes: [u8; 16] = rand::random();
+        bytes[6] = bytes[6] & 0x0f | 0x40;
+        bytes[8] = bytes[8] & 0x3f | 0x80;
+        Self(bytes)
+    }
+}
+
+impl CredentialId {
+    /// Reads the notation of `Display`.
+    fn parse(value: &str) -> Option<Self> {
+        let hex = value.replace('-', "");
+        let mut bytes = [0u8; 16];
+        if hex.len() != 2 * bytes.len() || !hex.is_ascii() {
+            return None;
+        }
+        for (byte, digits) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            *byte = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+        }
+        Some(Self(bytes))
+    }
+}
+
+impl fmt::Display for CredentialId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if [4, 6, 8, 10].contains(&i) {
+                f.write_str("-")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Timestamps are seconds since the Unix epoch, zero when unknown, as for credentials of older vaults.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Credential {
+    pub id: CredentialId,
+    pub title: String,
+    pub username: String,
+    pub secret: Secret,
+    pub description: String,
+    pub folder: String,
+    pub urls: Vec<String>,
+    pub tags: Vec<String>,
+    pub fields: Vec<CustomField>,
+    pub policy: Option<GeneratorPolicy>,
+    pub created: u64,
+    pub modified: u64,
+    pub used: u64,
+}
+
+impl Credential {
+    pub fn new(username: String, secret: Secret, description: String) -> Self {
+        Self {
+            id: CredentialId::random(),
+            title: String::new(),
+            username,
+            secret,
+            description,
+            folder: String::new(),
+            urls: Vec::new(),
+            tags: Vec::new(),
+            fields: Vec::new(),
+            policy: None,
+            created: 0,
+            modified: 0,
+            used: 0,
+        }
+    }
+
+    /// Current time in the resolution of the credential timestamps.
+    pub fn now() -> u64 {
+        time::SystemTime::now().duration_since(time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+    }
+}
+
+pub struct Storage {
+    cipher: Aes256Gcm,
+    legacy_cipher: Aes128,
+    key_check: [u8; KEY_CHECK_LEN],
+    kdf: Kdf,
+    salt: Vec<u8>,
+    directory: path::PathBuf,
+    file_name: String,
+    /// Shared with the storages the vault is re-keyed to, so that writes to the vault happen one at
+    /// a time and in order.
+    writer: Arc<Mutex<Writer>>,
+    generation: u64,
+}
+
+#[derive(Default)]
+struct Writer {
+    /// Generation of the current key. A storage of an older one may no longer write, as it would
+    /// bring the old key back.
+    generation: u64,
+    /// `Records::sequence` of the newest records written.
+    sequence: u64,
+}
+
+impl fmt::Debug for Storage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Storage").field("kdf", &self.kdf).field("directory", &self.directory).field("file_name", &self.file_name).finish_non_exhaustive()
+    }
+}
+
+impl Storage {
+    /// Vaults in the current format carry their own KDF parameters and salt in the header.
+    /// Legacy vaults use the legacy KDF and their salt file, new vaults get a fresh salt.
+    pub fn new_from_secret(secret: &str) -> Result<Self, StoreError> {
+        Self::open(secret, Self::directory())
+    }
+
+    fn open(secret: &str, directory: path::PathBuf) -> Result<Self, StoreError> {
+        let (kdf, salt) = match fs::read(directory.join(FILE_NAME)) {
+            Ok(buffer) if buffer.starts_with(MAGIC) => {
+                let header = Header::decode(&mut Reader::new(&buffer))?;
+                (header.kdf, header.salt)
+            },
+            Ok(buffer) => return Self::open_legacy(secret, &buffer, directory),
+            Err(_) => (Kdf::default(), Self::new_salt()?),
+        };
+        let key = kdf.derive(secret, &salt)?;
+        Ok(Self::new_from_key(&key, kdf, salt, directory))
+    }
+
+    /// The legacy cipher uses the first half of the key, which is exactly what PBKDF2 produced
+    /// when it was asked for a 16 byte key. Both ciphers wipe their expanded keys when the storage
+    /// is dropped, which happens once the last task holding it is done.
+    fn new_from_key(key: &[u8; 32], kdf: Kdf, salt: Vec<u8>, directory: path::PathBuf) -> Self {
+        Self {
+            cipher: Aes256Gcm::new(GenericArray::from_slice(key)),
+            legacy_cipher: Aes128::new(GenericArray::from_slice(&key[..16])),
+            key_check: Self::key_check(key),
+            kdf,
+            salt,
+            directory,
+            file_name: FILE_NAME.to_string(),
+            writer: Arc::default(),
+            generation: 0,
+        }
+    }
+
+    fn lock_writer(&self) -> Result<MutexGuard<'_, Writer>, StoreError> {
+        let writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        match writer.generation == self.generation {
+            true => Ok(writer),
+            false => Err(StoreError::Rekeyed),
+        }
+    }
+
+    fn key_check(key: &[u8; 32]) -> [u8; KEY_CHECK_LEN] {
+        let digest = Sha256::new().chain_update(b"pine key check").chain_update(key).finalize();
+        digest[..KEY_CHECK_LEN].try_into().unwrap_or_default()
+    }
+
+    pub fn kdf(&self) -> Kdf {
+        self.kdf
+    }
+
+    fn path(&self) -> path::PathBuf {
+        self.directory.join(&self.file_name)
+    }
+
+    fn is_legacy(&self) -> bool {
+        fs::read(self.path()).is_ok_and(|buffer| !buffer.starts_with(MAGIC))
+    }
+
+    fn backup_path(&self, backup: usize) -> path::PathBuf {
+        self.directory.join(format!("{}.{}", self.file_name, backup))
+    }
+
+    pub fn backups(&self) -> Vec<Backup> {
+        (1..).map_while(|index| {
+            let modified = fs::metadata(self.backup_path(index)).and_then(|metadata| metadata.modified()).ok()?;
+            Some(Backup { index, modified })
+        }).collect()
+    }
+
+    /// When credentials were last used is kept next to the vault as `id = seconds` lines, so that
+    /// recording a use does not rewrite the vault, possibly over a change another process made to it
+    /// meanwhile. Neither ids nor times are secret, like the config.
+    fn usage_path(&self) -> path::PathBuf {
+        self.directory.join(USAGE_FILE_NAME)
+    }
+
+    fn read_usage(&self) -> HashMap<CredentialId, u64> {
+        let content = fs::read_to_string(self.usage_path()).unwrap_or_default();
+        content.lines().filter_map(|line| {
+            let (id, used) = line.split_once('=')?;
+            Some((CredentialId::parse(id.trim())?, used.trim().parse().ok()?))
+        }).collect()
+    }
+
+    fn write_usage(&self, usage: &HashMap<CredentialId, u64>) -> Result<(), StoreError> {
+        let content = usage.iter().map(|(id, used)| format!("{} = {}\n", id, used)).collect::<String>();
+        fs::create_dir_all(&self.directory).map_err(StoreError::IO)?;
+        write_atomic(&self.usage_path(), content.as_bytes()).map_err(StoreError::IO)
+    }
+
+    /// Usage times are only ever moved forward, the vault may carry newer ones of another frontend.
+    fn with_usage(&self, mut creds: Vec<Credential>) -> Vec<Credential> {
+        let usage = self.read_usage();
+        for cred in &mut creds {
+            cred.used = cred.used.max(usage.get(&cred.id).copied().unwrap_or_default());
+        }
+        creds
+    }
+
+    /// Shifts `file.1` to `file.2` and so on, copies the current vault to `file.1` and drops
+    /// backups beyond `count`. Legacy vaults are not backed up, as they were encrypted per block.
+    fn rotate_backups(&self, count: usize) -> io::Result<()> {
+        let mut excess = count + 1;
+        while self.backup_path(excess).is_file() {
+            fs::remove_file(self.backup_path(excess))?;
+            excess += 1;
+        }
+        if count == 0 || !self.path().is_file() || self.is_legacy() {
+            return Ok(());
+        }
+        for backup in (1..count).rev() {
+            if self.backup_path(backup).is_file() {
+                fs::rename(self.backup_path(backup), self.backup_path(backup + 1))?;
+            }
+        }
+        fs::copy(self.path(), self.backup_path(1)).map(|_| ())
+    }
+
+    /// Platform specific directory holding the vault, its backups, the usage file and the config file.
+    pub fn directory() -> path::PathBuf {
+        let home_dir = home::home_dir().unwrap_or_default();
+        if cfg!(target_os = "windows") {
+            home_dir.join("AppData").join(TITLE.to_lowercase())
+        }
+        else if cfg!(target_os = "macos") {
+            home_dir.join("Library").join("Application Support").join(TITLE.to_lowercase())
+        }
+        else {
+            home_dir.join(".config").join(TITLE.to_lowercase())
+        }
+    }
+
+    fn new_salt() -> Result<Vec<u8>, StoreError> {
+        let mut salt = vec!{0u8; SALT_LEN};
+        rand::thread_rng().try_fill_bytes(&mut salt).map_err(StoreError::Rand)?;
+        Ok(salt)
+    }
+
+    /// Before the salt moved into the header it was kept in a randomly named `*salt` file, next to
+    /// decoys written after it. Each one is tried, newest first, until one of them decrypts the
+    /// vault. Modification time stands in for creation time on filesystems that do not record it.
+    fn open_legacy(secret: &str, buffer: &[u8], directory: path::PathBuf) -> Result<Self, StoreError> {
+        let mut files = Self::legacy_salt_files(&directory);
+        files.sort_by_key(|(_, time)| std::cmp::Reverse(*time));
+        let mut result = Err(StoreError::MissingSalt);
+        for (file, _) in files {
+            let salt = match fs::read(file) {
+                Ok(salt) => salt,
+                Err(error) => {
+                    result = Err(StoreError::IO(error));
+                    continue;
+                },
+            };
+            let key = Kdf::LEGACY.derive(secret, &salt)?;
+            let storage = Self::new_from_key(&key, Kdf::LEGACY, salt, directory.clone());
+            match read_vault(&storage, buffer) {
+                Ok(_) => return Ok(storage),
+                Err(error) => result = Err(error),
+            }
+        }
+        result
+    }
+
+    fn legacy_salt_files(directory: &path::Path) -> Vec<(path::PathBuf, time::SystemTime)> {
+        const SALT_EXTENTION: &str = "salt";
+        let Ok(read_dir) = fs::read_dir(directory) else { return Vec::new() };
+        read_dir.filter_map(|file| {
+            let file = file.ok()?;
+            let metadata = file.metadata().ok().filter(fs::Metadata::is_file)?;
+            file.file_name().to_str().filter(|name| name.ends_with(SALT_EXTENTION))?;
+            let time = metadata.created().or_else(|_| metadata.modified()).unwrap_or(time::UNIX_EPOCH);
+            Some((file.path(), time))
+        }).collect()
+    }
+
+    /// Salt files are only needed until the vault has been written with a header. One that cannot be
+    /// deleted is no longer read either, and is tried again on the next unlock.
+    fn remove_legacy_salt_files(&self) {
+        if self.is_legacy() {
+            return;
+        }
+        for (path, _) in Self::legacy_salt_files(&self.directory) {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum StoreError {
+    IO(io::Error),
+    Rand(rand::Error),
+    PadError,
+    UnpadError,
+    InstallationError,
+    InvalidSecret,
+    Integrity,
+    UnsupportedFormat,
+    Kdf(argon2::Error),
+    MissingSalt,
+    Rekeyed,
+    KdfBounds,
+}
+
+impl From<StoreError> for String {
+    fn from(error: StoreError) -> Self {
+        match error {
+            StoreError::IO(io_error) => io_error.to_string(),
+            StoreError::PadError => String::from("error while padding"),
+            StoreError::UnpadError => String::from("error while unpadding"),
+            StoreError::InstallationError => String::from("error while installing application"),
+            StoreError::InvalidSecret => String::from("invalid master password"),
+            StoreError::Integrity => String::from("vault is corrupted or has been tampered with"),
+            StoreError::UnsupportedFormat => String::from("vault was written by an unsupported version"),
+            StoreError::Kdf(kdf_error) => kdf_error.to_string(),
+            StoreError::MissingSalt => String::from("salt file of the vault is missing"),
+            StoreError::Rekeyed => String::from("vault was re-keyed in the meantime"),
+            StoreError::KdfBounds => String::from("key derivation parameters are out of bounds"),
+            StoreError::Rand(rand_error) => rand_error.to_string(),
+        }
+    }
+}
+
+/// Plaintext of a vault, wiped when dropped. Credentials are encoded one at a time, so a frontend
+/// that keeps them in its own types can save them without copying all of them first.
+pub struct Records {
+    content: Zeroizing<Vec<u8>>,
+    ids: HashSet<CredentialId>,
+    /// Order in which the records were taken. Saves run concurrently and may reach the vault out of
+    /// order, so records older than those written already are dropped.
+    sequence: u64,
+}
+
+impl Records {
+    pub fn new<C: Borrow<Credential>>(creds: impl ExactSizeIterator<Item = C>) -> Self {
+        static SEQUENCE: AtomicU64 = AtomicU64::new(1);
+        let mut ids = HashSet::with_capacity(creds.len());
+        let content = encode_records(creds.inspect(|cred| { ids.insert(cred.borrow().id); }));
+        Self { content, ids, sequence: SEQUENCE.fetch_add(1, atomic::Ordering::Relaxed) }
+    }
+}
+
+impl From<Vec<Credential>> for Records {
+    fn from(creds: Vec<Credential>) -> Self {
+        Self::new(creds.iter())
+    }
+}
+
+/// Nothing is written if records taken after `records` have been saved already. Usage times of
+/// credentials that are no longer in `records` are dropped as well.
+pub async fn save(storage: Arc<Storage>, records: impl Into<Records>) -> Result<(), StoreError> {
+    let records = records.into();
+    if !commit(&storage, &records)? {
+        return Ok(());
+    }
+    let mut usage = storage.read_usage();
+    let count = usage.len();
+    usage.retain(|id, _| records.ids.contains(id));
+    match usage.len() == count {
+        true => Ok(()),
+        false => storage.write_usage(&usage),
+    }
+}
+
+/// Records that the credential `id` was used at `used`. Only the usage file next to the vault is
+/// rewritten, so reading a credential never writes the vault.
+pub async fn touch(storage: Arc<Storage>, id: CredentialId, used: u64) -> Result<(), StoreError> {
+    let _writer = storage.lock_writer()?;
+    let mut usage = storage.read_usage();
+    usage.insert(id, used);
+    storage.write_usage(&usage)
+}
+
+fn seal(storage: &Storage, Records { content, .. }: &Records) -> Result<Vec<u8>, StoreError> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().try_fill_bytes(&mut nonce).map_err(StoreError::Rand)?;
+    let header = Header {
+        kdf: storage.kdf,
+        salt: storage.salt.clone(),
+        key_check: storage.key_check,
+        nonce,
+        version: FORMAT_VERSION,
+    }.encode();
+
+    let payload = Payload { msg: content.as_slice(), aad: header.as_slice() };
+    let ciphertext = storage.cipher.encrypt(Nonce::from_slice(&nonce), payload).map_err(|_| StoreError::Integrity)?;
+    let mut buffer = header;
+    buffer.extend_from_slice(ciphertext.as_slice());
+    Ok(buffer)
+}
+
+/// Writes `records` unless newer ones have been written meanwhile, and tells whether it did.
+fn commit(storage: &Storage, records: &Records) -> Result<bool, StoreError> {
+    let buffer = seal(storage, records)?;
+    let mut writer = storage.lock_writer()?;
+    if records.sequence < writer.sequence {
+        return Ok(false);
+    }
+    writer.sequence = records.sequence;
+    replace(storage, &buffer).map(|_| true)
+}
+
+/// Like `commit`, for callers already holding the writer.
+fn replace(storage: &Storage, buffer: &[u8]) -> Result<(), StoreError> {
+    // The new vault is flushed to a temporary file and renamed over the old one, so a crash or
+    // a full disk leaves either the old or the new vault behind, never a partial one.
+    fs::create_dir_all(&storage.directory).map_err(StoreError::IO)?;
+    let config = Config::load_from(&storage.directory);
+    let temp_path = temp_path(&storage.path());
+    write_synced(&temp_path, buffer)
+        .and_then(|_| storage.rotate_backups(config.backups))
+        .and_then(|_| fs::rename(&temp_path, storage.path()))
+        .and_then(|_| sync_directory(&storage.directory))
+        .map_err(|e| {
+            let _ = fs::remove_file(&temp_path);
+            StoreError::IO(e)
+        })
+}
+
+pub async fn fetch(storage: Arc<Storage>) -> Result<Vec<Credential>, StoreError> {
+    read(&storage)
+}
+
+fn read(storage: &Storage) -> Result<Vec<Credential>, StoreError> {
+    let buffer = fs::read(storage.path()).map_err(StoreError::IO)?;
+    Ok(storage.with_usage(read_vault(storage, &buffer)?))
+}
+
+/// Replaces the vault with the contents of a backup, re-encrypted with the current key. The
+/// backup may predate a re-key, so its key is derived from the master password and its own header.
+/// The replaced vault becomes the newest backup, so a restore can itself be undone.
+pub async fn restore(storage: Arc<Storage>, secret: String, backup: usize) -> Result<Vec<Credential>, StoreError> {
+    let secret = Zeroizing::new(secret);
+    blocking(move || {
+        let buffer = fs::read(storage.backup_path(backup)).map_err(StoreError::IO)?;
+        let header = Header::decode(&mut Reader::new(&buffer))?;
+        let key = header.kdf.derive(&secret, &header.salt)?;
+        let backup_storage = Storage::new_from_key(&key, header.kdf, header.salt, storage.directory.clone());
+        let creds = read_vault(&backup_storage, &buffer)?;
+        commit(&storage, &Records::new(creds.iter()))?;
+        Ok(storage.with_usage(creds))
+    }).await
+}
+
+/// Runs `task` on a thread of its own. Key derivation is slow by design, and would otherwise stall
+/// everything else the executor of the caller runs meanwhile.
+async fn blocking<T: Send + 'static>(task: impl FnOnce() -> T + Send + 'static) -> T {
+    let (sender, receiver) = futures_channel::oneshot::channel();
+    let thread = std::thread::spawn(move || {
+        let _ = sender.send(task());
+    });
+    match receiver.await {
+        Ok(value) => value,
+        Err(_) => std::panic::resume_unwind(thread.join().err().unwrap_or_else(|| Box::new("task ended without a result"))),
+    }
+}
+
+fn read_vault(storage: &Storage, buffer: &[u8]) -> Result<Vec<Credential>, StoreError> {
+    match buffer.starts_with(MAGIC) {
+        true => {
+            let (header, content) = decrypt(storage, buffer)?;
+            match header.version {
+                CSV_FORMAT_VERSION => decode_csv_records(content),
+                version => decode_records(&content, version),
+            }
+        },
+        false => decode_csv_records(decrypt_legacy(storage, buffer)?),
+    }
+}
+
+/// A fresh name next to `path` for every write, so concurrent writes, even of other processes,
+/// never share a temporary file.
+fn temp_path(path: &path::Path) -> path::PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_owned();
+    file_name.push(format!(".{:016x}.tmp", rand::random::<u64>()));
+    path.with_file_name(file_name)
+}
+
+fn write_synced(path: &path::Path, content: &[u8]) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new().write(true).create_new(true).open(path)?;
+    file.write_all(content)?;
+    file.sync_all()
+}
+
+/// Makes a completed rename durable. Only needed, and only possible, on unix.
+fn sync_directory(directory: &path::Path) -> io::Result<()> {
+    if cfg!(unix) {
+        fs::File::open(directory)?.sync_all()?;
+    }
+    Ok(())
+}
+
+fn write_atomic(path: &path::Path, content: &[u8]) -> io::Result<()> {
+    let temp_path = temp_path(path);
+    write_synced(&temp_path, content).and_then(|_| fs::rename(&temp_path, path))
+}
+
+fn write_str(buffer: &mut Vec<u8>, value: &str) {
+    buffer.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+/// Length of the record `encode_records` writes for `cred`.
+fn record_len(cred: &Credential) -> usize {
+    let str_len = |value: &str| 4 + value.len();
+    let list_len = |list: &[String]| 4 + list.iter().map(|value| str_len(value)).sum::<usize>();
+    let policy_len = cred.policy.as_ref().map_or(0, |policy| 4 + 1 + str_len(&policy.symbol_set) + 4 + 1 + str_len(&policy.separator));
+    let fields_len = 4 + cred.fields.iter().map(|field| str_len(&field.name) + 1 + str_len(&field.value)).sum::<usize>();
+    str_len(&cred.username) + 1 + str_len(cred.secret.value()) + str_len(&cred.description) + 1 + policy_len + str_len(&cred.title)
+        + list_len(&cred.urls) + list_len(&cred.tags) + fields_len + str_len(&cred.folder) + 3 * 8 + cred.id.0.len()
+}
+
+/// Grows `buffer` by at least `additional` bytes, wiping the old allocation that `Vec` would leave behind.
+fn reserve(buffer: &mut Zeroizing<Vec<u8>>, additional: usize) {
+    if buffer.capacity() - buffer.len() < additional {
+        let mut grown = Zeroizing::new(Vec::with_capacity((buffer.len() + additional).max(2 * buffer.capacity())));
+        grown.extend_from_slice(buffer);
+        *buffer = grown;
+    }
+}
+
+/// `count | (username | secret kind | secret | description | policy | title | urls | tags | fields | folder
+/// | created | modified | used | id)*` with every string prefixed by its length. The policy is a presence
+/// byte, followed by `length | flags | symbol set | words | passphrase flags | separator` when present.
+/// Urls and tags are a count followed by that many strings, fields a count followed by `name | kind |
+/// value` each. Timestamps are 64 bit, ids 16 bytes.
+fn encode_records<C: Borrow<Credential>>(creds: impl ExactSizeIterator<Item = C>) -> Zeroizing<Vec<u8>> {
+    let mut buffer = Zeroizing::new(Vec::new());
+    buffer.extend_from_slice(&(creds.len() as u32).to_le_bytes());
+    for cred in creds {
+        let cred = cred.borrow();
+        reserve(&mut buffer, record_len(cred));
+        let kind = match cred.secret.kind() {
+            SecretKind::Password => SECRET_PASSWORD,
+            SecretKind::Passphrase => SECRET_PASSPHRASE,
+            SecretKind::Pin => SECRET_PIN,
+        };
+        write_str(&mut buffer, &cred.username);
+        buffer.push(kind);
+        write_str(&mut buffer, cred.secret.value());
+        write_str(&mut buffer, &cred.description);
+        match &cred.policy {
+            Some(policy) => {
+                buffer.push(1);
+                policy.encode(&mut buffer);
+            },
+            None => buffer.push(0),
+        }
+        write_str(&mut buffer, &cred.title);
+        for list in [&cred.urls, &cred.tags] {
+            buffer.extend_from_slice(&(list.len() as u32).to_le_bytes());
+            list.iter().for_each(|value| write_str(&mut buffer, value));
+        }
+        buffer.extend_from_slice(&(cred.fields.len() as u32).to_le_bytes());
+        for field in &cred.fields {
+            write_str(&mut buffer, &field.name);
+            buffer.push(match field.kind {
+                FieldKind::Text => FIELD_TEXT,
+                FieldKind::Hidden => FIELD_HIDDEN,
+                FieldKind::Url => FIELD_URL,
+                FieldKind::Email => FIELD_EMAIL,
+                FieldKind::Totp => FIELD_TOTP,
+            });
+            write_str(&mut buffer, &field.value);
+        }
+        write_str(&mut buffer, &cred.folder);
+        for timestamp in [cred.created, cred.modified, cred.used] {
+            buffer.extend_from_slice(&timestamp.to_le_bytes());
+        }
+        buffer.extend_from_slice(&cred.id.0);
+    }
+    buffer
+}
+
+/// Records of version 2 vaults end with the description, those of versions 3 and 4 with the policy and
+/// those of version 5 with the custom fields and those of version 6 with the timestamps. Custom fields of
+/// version 7 vaults cannot hold TOTP keys.
+fn decode_records(content: &[u8], version: u8) -> Result<Vec<Credential>, StoreError> {
+    let mut reader = Reader::new(content);
+    let count = reader.u32()?;
+    let creds = (0..count).map(|_| {
+        let username = reader.string()?;
+        let secret = match reader.u8()? {
+            SECRET_PASSWORD => Secret::Password(reader.string()?),
+            SECRET_PIN => Secret::Pin(reader.string()?),
+            SECRET_PASSPHRASE => Secret::Passphrase(reader.string()?),
+            _ => return Err(StoreError::Integrity),
+        };
+        let description = reader.string()?;
+        let policy = match version {
+            BINARY_FORMAT_VERSION => None,
+            _ => match reader.u8()? {
+                0 => None,
+                1 => Some(GeneratorPolicy::decode(&mut reader, version)?),
+                _ => return Err(StoreError::Integrity),
+            },
+        };
+        if version <= PLAIN_RECORD_FORMAT_VERSION {
+            return Ok(Credential { policy, ..Credential::new(username, secret, description) });
+        }
+        let title = reader.string()?;
+        let mut strings = || (0..reader.u32()?).map(|_| reader.string()).collect::<Result<Vec<_>, StoreError>>();
+        let urls = strings()?;
+        let tags = strings()?;
+        let fields = (0..reader.u32()?).map(|_| {
+            let name = reader.string()?;
+            let kind = match reader.u8()? {
+                FIELD_TEXT => FieldKind::Text,
+                FIELD_HIDDEN => FieldKind::Hidden,
+                FIELD_URL => FieldKind::Url,
+                FIELD_EMAIL => FieldKind::Email,
+                FIELD_TOTP if version > ID_FORMAT_VERSION => FieldKind::Totp,
+                _ => return Err(StoreError::Integrity),
+            };
+            Ok(CustomField { name, kind, value: reader.string()? })
+        }).collect::<Result<Vec<_>, StoreError>>()?;
+        let cred = Credential { title, urls, tags, fields, policy, ..Credential::new(username, secret, description) };
+        if version == DETAILS_FORMAT_VERSION {
+            return Ok(cred);
+        }
+        let cred = Credential { folder: reader.string()?, created: reader.u64()?, modified: reader.u64()?, used: reader.u64()?, ..cred };
+        if version == BOOKKEEPING_FORMAT_VERSION {
+            return Ok(cred);
+        }
+        Ok(Credential { id: CredentialId(reader.array()?), ..cred })
+    }).collect::<Result<Vec<_>, StoreError>>()?;
+    match reader.position == content.len() {
+        true => Ok(creds),
+        false => Err(StoreError::Integrity),
+    }
+}
+
+/// Records of legacy vaults, one `username,kind:secret,description` line each.
+fn decode_csv_records(mut content: Zeroizing<Vec<u8>>) -> Result<Vec<Credential>, StoreError> {
+    let content = Zeroizing::new(String::from_utf8(std::mem::take(&mut *content)).map_err(|error| {
+        // Wipes the bytes that failed to decode, which are the plaintext of a vault all the same.
+        drop(Zeroizing::new(error.into_bytes()));
+        StoreError::InvalidSecret
+    })?);
+    content.lines().map(|buffer| {
+        let mut iter = buffer.split(',');
+        let username = iter.next().ok_or(StoreError::InvalidSecret)?.to_owned();
+        let secret = iter.next().and_then(Secret::parse).ok_or(StoreError::InvalidSecret)?;
+        let description = iter.next().unwrap_or_default().to_owned();
+        Ok(Credential::new(username, secret, description))
+    }).collect()
+}
+
+fn decrypt(storage: &Storage, buffer: &[u8]) -> Result<(Header, Zeroizing<Vec<u8>>), StoreError> {
+    let mut reader = Reader::new(buffer);
+    let header = Header::decode(&mut reader)?;
+    if header.key_check != storage.key_check {
+        return Err(StoreError::InvalidSecret);
+    }
+    let (aad, ciphertext) = buffer.split_at(reader.position);
+    let payload = Payload { msg: ciphertext, aad };
+    let content = storage.cipher.decrypt(Nonce::from_slice(&header.nonce), payload).map_err(|_| StoreError::Integrity)?;
+    Ok((header, Zeroizing::new(content)))
+}
+
+/// Reads vaults written before the versioned format, where every 16 byte block was encrypted on its own.
+fn decrypt_legacy(storage: &Storage, buffer: &[u8]) -> Result<Zeroizing<Vec<u8>>, StoreError> {
+    // Sized up front, so the plaintext is never left behind by a reallocation.
+    let mut decrypted_buffer = Zeroizing::new(Vec::with_capacity(buffer.len()));
+    for chunk in buffer.chunks(16) {
+        if chunk.len() < 16 {
+            return Err(StoreError::PadError);
+        }
+        let mut block_array = GenericArray::from_slice(chunk).to_owned();
+        storage.legacy_cipher.decrypt_block(&mut block_array);
+        let content = Pkcs7::unpad(&block_array)?;
+        decrypted_buffer.extend_from_slice(content);
+        block_array.as_mut_slice().zeroize();
+    }
+    Ok(decrypted_buffer)
+}
+
+pub type Unlocked = (Arc<Storage>, Vec<Credential>);
+
+pub fn vault_exists() -> bool {
+    Storage::directory().join(FILE_NAME).is_file()
+}
+
+/// Derives the key from the master password and verifies it by decrypting the vault.
+/// A missing vault is treated as empty, so the first password entered creates it.
+pub async fn unlock(secret: String) -> Result<Unlocked, StoreError> {
+    open(secret, Storage::directory()).await
+}
+
+/// Legacy vaults are rewritten in the current format once they have been read successfully,
+/// after which their salt files are no longer needed.
+pub async fn open(secret: String, directory: path::PathBuf) -> Result<Unlocked, StoreError> {
+    let secret = Zeroizing::new(secret);
+    blocking(move || {
+        let storage = Arc::new(Storage::open(&secret, directory)?);
+        let creds = match read(&storage) {
+            Ok(creds) => creds,
+            Err(StoreError::IO(e)) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        if storage.is_legacy() {
+            commit(&storage, &Records::new(creds.iter()))?;
+        }
+        storage.remove_legacy_salt_files();
+        Ok((storage, creds))
+    }).await
+}
+
+/// Re-encrypts the vault with a key derived by `kdf` from a fresh salt, keeping the master password.
+pub async fn rekey(storage: Arc<Storage>, secret: String, kdf: Kdf) -> Result<Arc<Storage>, StoreError> {
+    change_secret(storage, secret.clone(), secret, kdf).await.map(|(storage, _)| storage)
+}
+
+/// Re-encrypts the vault with a key derived by `kdf` from `new_secret` and a fresh salt. The current
+/// master password is checked first, so a typo cannot leave the vault behind an unknown key, and
+/// the old vault is only replaced once the new one has been decrypted again. Saves still holding
+/// `storage` fail with `Rekeyed` afterwards. A new master password is also applied to the backups,
+/// see `reencrypt_backups`, along with the number of backups that had to be deleted.
+pub async fn change_secret(storage: Arc<Storage>, secret: String, new_secret: String, kdf: Kdf) -> Result<(Arc<Storage>, usize), StoreError> {
+    let (secret, new_secret) = (Zeroizing::new(secret), Zeroizing::new(new_secret));
+    if !kdf.is_valid() {
+        return Err(StoreError::KdfBounds);
+    }
+    blocking(move || {
+        let current_key = storage.kdf.derive(&secret, &storage.salt)?;
+        if Storage::key_check(&current_key) != storage.key_check {
+            return Err(StoreError::InvalidSecret);
+        }
+        let salt = Storage::new_salt()?;
+        let key = kdf.derive(&new_secret, &salt)?;
+        let changed = Storage {
+            writer: Arc::clone(&storage.writer),
+            generation: storage.generation + 1,
+            ..Storage::new_from_key(&key, kdf, salt, storage.directory.clone())
+        };
+        let mut writer = storage.lock_writer()?;
+        let creds = match fs::read(storage.path()) {
+            Ok(buffer) => read_vault(&storage, &buffer)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(StoreError::IO(e)),
+        };
+        let records = Records::new(creds.iter());
+        let buffer = seal(&changed, &records)?;
+        if read_vault(&changed, &buffer)? != creds {
+            return Err(StoreError::Integrity);
+        }
+        replace(&changed, &buffer)?;
+        let deleted = match secret == new_secret {
+            true => 0,
+            false => reencrypt_backups(&changed, &secret),
+        };
+        (writer.generation, writer.sequence) = (changed.generation, records.sequence);
+        drop(writer);
+        Ok((Arc::new(changed), deleted))
+    }).await
+}
+
+/// Re-encrypts every backup for `changed`, so the old master password `secret` opens none of them
+/// anymore. Backups it cannot re-encrypt, like those of a master password before `secret`, are
+/// deleted instead and the rest renumbered. Returns how many were deleted. The vault has been
+/// changed already, so this cannot fail.
+fn reencrypt_backups(changed: &Storage, secret: &str) -> usize {
+    let mut keys: Vec<(Kdf, Vec<u8>, Storage)> = Vec::new();
+    let mut kept = 0;
+    let backups = changed.backups();
+    for backup in &backups {
+        let path = changed.backup_path(backup.index);
+        let reencrypted = fs::read(&path).map_err(StoreError::IO).and_then(|buffer| {
+            let header = Header::decode(&mut Reader::new(&buffer))?;
+            let position = match keys.iter().position(|(kdf, salt, _)| *kdf == header.kdf && *salt == header.salt) {
+                Some(position) => position,
+                None => {
+                    let key = header.kdf.derive(secret, &header.salt)?;
+                    let storage = Storage::new_from_key(&key, header.kdf, header.salt.clone(), changed.directory.clone());
+                    keys.push((header.kdf, header.salt, storage));
+                    keys.len() - 1
+                },
+            };
+            let creds = read_vault(&keys[position].2, &buffer)?;
+            write_atomic(&path, &seal(changed, &Records::new(creds.iter()))?).map_err(StoreError::IO)
+        });
+        match reencrypted {
+            Ok(_) => {
+                kept += 1;
+                if backup.index != kept {
+                    let _ = fs::rename(&path, changed.backup_path(kept));
+                }
+            },
+            Err(_) => {
+                let _ = fs::remove_file(&path);
+            },
+        }
+    }
+    backups.len() - kept
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Backup {
+    pub index: usize,
+    pub modified: time::SystemTime,
+}
+
+/// Preferences kept next to the vault as `key = value` lines. They are not secret, so they are
+/// stored in plain text and can be read before the vault is unlocked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub backups: usize,
+    pub sort: SortMode,
+    pub grouping: Grouping,
+    /// How long a revealed secret stays visible, within `REVEAL_SECONDS`.
+    pub reveal_seconds: u64,
+    /// How long a copied secret stays on the clipboard, within `CLIPBOARD_SECONDS`.
+    pub clipboard_seconds: u64,
+    /// Minutes without input after which the vault locks, never if zero.
+    pub idle_lock_minutes: u64,
+    pub lock_on_minimize: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self { backups: 3, sort: SortMode::default(), grouping: Grouping::default(), reveal_seconds: 5, clipboard_seconds: 20, idle_lock_minutes: 5, lock_on_minimize: true }
+    }
+}
+
+impl Config {
+    const FILE_NAME: &'static str = "config";
+    pub const REVEAL_SECONDS: RangeInclusive<u64> = 1..=3600;
+    pub const CLIPBOARD_SECONDS: RangeInclusive<u64> = 1..=3600;
+
+    pub fn load() -> Self {
+        Self::load_from(&Storage::directory())
+    }
+
+    fn load_from(directory: &path::Path) -> Self {
+        let mut config = Self::default();
+        let content = fs::read_to_string(directory.join(Self::FILE_NAME)).unwrap_or_default();
+        for (key, value) in content.lines().filter_map(|line| line.split_once('=')) {
+            let value = value.trim();
+            match key.trim() {
+                "backups" => config.backups = value.parse().unwrap_or(config.backups),
+                "sort" => config.sort = SortMode::ALL.into_iter().find(|sort| sort.key() == value).unwrap_or(config.sort),
+                "grouping" => config.grouping = Grouping::ALL.into_iter().find(|grouping| grouping.key() == value).unwrap_or(config.grouping),
+                "reveal_seconds" => config.reveal_seconds = value.parse().ok().filter(|seconds| Self::REVEAL_SECONDS.contains(seconds)).unwrap_or(config.reveal_seconds),
+                "clipboard_seconds" => config.clipboard_seconds = value.parse().ok().filter(|seconds| Self::CLIPBOARD_SECONDS.contains(seconds)).unwrap_or(config.clipboard_seconds),
+                "idle_lock_minutes" => config.idle_lock_minutes = value.parse().unwrap_or(config.idle_lock_minutes),
+                "lock_on_minimize" => config.lock_on_minimize = value.parse().unwrap_or(config.lock_on_minimize),
+                _ => (),
+            }
+        }
+        config
+    }
+
+    fn write_to(&self, directory: &path::Path) -> Result<(), StoreError> {
+        let content = format!("backups = {}\nsort = {}\ngrouping = {}\nreveal_seconds = {}\nclipboard_seconds = {}\nidle_lock_minutes = {}\nlock_on_minimize = {}\n", self.backups, self.sort.key(), self.grouping.key(), self.reveal_seconds, self.clipboard_seconds, self.idle_lock_minutes, self.lock_on_minimize);
+        fs::create_dir_all(directory).map_err(StoreError::IO)?;
+        write_atomic(&directory.join(Self::FILE_NAME), content.as_bytes()).map_err(StoreError::IO)
+    }
+}
+
+pub async fn save_config(config: Config) -> Result<(), StoreError> {
+    config.write_to(&Storage::directory())
+}
+
+/// Order of the credential list. Credentials of older vaults have no timestamps, so they count as
+/// the oldest and keep their vault order among themselves.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortMode {
+    /// By title, or by username for credentials without one.
+    Alphabetical,
+    RecentlyUsed,
+    RecentlyModified,
+    /// Oldest first, which is the vault order.
+    #[default]
+    Created,
+}
+
+impl SortMode {
+    pub const ALL: [Self; 4] = [Self::Alphabetical, Self::RecentlyUsed, Self::RecentlyModified, Self::Created];
+
+    fn key(&self) -> &'static str {
+        match self {
+            Self::Alphabetical => "alphabetical",
+            Self::RecentlyUsed => "used",
+            Self::RecentlyModified => "modified",
+            Self::Created => "created",
+        }
+    }
+}
+
+impl fmt::Display for SortMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Alphabetical => "Alphabetical",
+            Self::RecentlyUsed => "Recently used",
+            Self::RecentlyModified => "Recently modified",
+            Self::Created => "Creation date",
+        }.fmt(f)
+    }
+}
+
+/// Sections of the credential list. Credentials are listed under each of their tags.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Grouping {
+    #[default]
+    None,
+    Tag,
+    Folder,
+}
+
+impl Grouping {
+    pub const ALL: [Self; 3] = [Self::None, Self::Tag, Self::Folder];
+
+    fn key(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Tag => "tag",
+            Self::Folder => "folder",
+        }
+    }
+}
+
+impl fmt::Display for Grouping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => "None",
+            Self::Tag => "Tag",
+            Self::Folder => "Folder",
+        }.fmt(f)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kdf {
+    Pbkdf2 { iterations: u32 },
+    Argon2id { memory_kib: u32, iterations: u32, parallelism: u32 },
+}
+
+impl Default for Kdf {
+    fn default() -> Self {
+        Self::Argon2id { memory_kib: 64 * 1024, iterations: 3, parallelism: 1 }
+    }
+}
+
+impl fmt::Display for Kdf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pbkdf2 { iterations } => write!(f, "PBKDF2-HMAC-SHA256, {} iterations", iterations),
+            Self::Argon2id { memory_kib, iterations, parallelism } => write!(f, "Argon2id, {} MiB, {} iterations, {} lanes", memory_kib / 1024, iterations, parallelism),
+        }
+    }
+}
+
+impl Kdf {
+    /// Parameters every vault was derived with before the KDF was recorded in the header.
+    pub const LEGACY: Self = Self::Pbkdf2 { iterations: 100_000 };
+    const PBKDF2_HMAC_SHA256: u8 = 1;
+    const ARGON2ID: u8 = 2;
+    /// Bounds of the parameters. Past them a vault would take minutes or gigabytes to unlock, so a
+    /// header asking for more is rejected before anything is derived.
+    pub const PBKDF2_ITERATIONS: RangeInclusive<u32> = 1..=10_000_000;
+    pub const ARGON2_MEMORY_KIB: RangeInclusive<u32> = 8..=2 * 1024 * 1024;
+    pub const ARGON2_ITERATIONS: RangeInclusive<u32> = 1..=64;
+    pub const ARGON2_PARALLELISM: RangeInclusive<u32> = 1..=16;
+
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Self::Pbkdf2 { iterations } => Self::PBKDF2_ITERATIONS.contains(iterations),
+            Self::Argon2id { memory_kib, iterations, parallelism } => Self::ARGON2_MEMORY_KIB.contains(memory_kib) && Self::ARGON2_ITERATIONS.contains(iterations) && Self::ARGON2_PARALLELISM.contains(parallelism),
+        }
+    }
+
+    fn derive(&self, secret: &str, salt: &[u8]) -> Result<Zeroizing<[u8; 32]>, StoreError> {
+        let mut key = Zeroizing::new([0u8; 32]);
+        match self {
+            Self::Pbkdf2 { iterations } => pbkdf2::pbkdf2_hmac::<Sha256>(secret.as_bytes(), salt, *iterations, key.as_mut()),
+            Self::Argon2id { memory_kib, iterations, parallelism } => {
+                let params = argon2::Params::new(*memory_kib, *iterations, *parallelism, Some(32)).map_err(StoreError::Kdf)?;
+                argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params).hash_password_into(secret.as_bytes(), salt, key.as_mut()).map_err(StoreError::Kdf)?;
+            },
+        }
+        Ok(key)
+    }
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        match self {
+            Self::Pbkdf2 { iterations } => {
+                buffer.push(Self::PBKDF2_HMAC_SHA256);
+                buffer.extend_from_slice(&iterations.to_le_bytes());
+            },
+            Self::Argon2id { memory_kib, iterations, parallelism } => {
+                buffer.push(Self::ARGON2ID);
+                buffer.extend_from_slice(&memory_kib.to_le_bytes());
+                buffer.extend_from_slice(&iterations.to_le_bytes());
+                buffer.extend_from_slice(&parallelism.to_le_bytes());
+            },
+        }
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Self, StoreError> {
+        let kdf = match reader.u8()? {
+            Self::PBKDF2_HMAC_SHA256 => Self::Pbkdf2 { iterations: reader.u32()? },
+            Self::ARGON2ID => Self::Argon2id { memory_kib: reader.u32()?, iterations: reader.u32()?, parallelism: reader.u32()? },
+            _ => return Err(StoreError::UnsupportedFormat),
+        };
+        match kdf.is_valid() {
+            true => Ok(kdf),
+            false => Err(StoreError::KdfBounds),
+        }
+    }
+}
+
+/// Plaintext prefix of every vault, also passed to the cipher as associated data.
+///
+/// `magic | version | kdf id | kdf parameters | cipher id | salt length | salt | key check | nonce`
+struct Header {
+    version: u8,
+    kdf: Kdf,
+    salt: Vec<u8>,
+    key_check: [u8; KEY_CHECK_LEN],
+    nonce: [u8; NONCE_LEN],
+}
+
+impl Header {
+    fn encode(&self) -> Vec<u8> {
+        let mut buffer = Vec::from(MAGIC);
+        buffer.push(self.version);
+        self.kdf.encode(&mut buffer);
+        buffer.push(CIPHER_AES_256_GCM);
+        buffer.push(self.salt.len() as u8);
+        buffer.extend_from_slice(&self.salt);
+        buffer.extend_from_slice(&self.key_check);
+        buffer.extend_from_slice(&self.nonce);
+        buffer
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Self, StoreError> {
+        let (magic, version) = (reader.take(MAGIC.len())?, reader.u8()?);
+        if magic != MAGIC || !(CSV_FORMAT_VERSION..=FORMAT_VERSION).contains(&version) {
+            return Err(StoreError::UnsupportedFormat);
+        }
+        let kdf = Kdf::decode(reader)?;
+        if reader.u8()? != CIPHER_AES_256_GCM {
+            return Err(StoreError::UnsupportedFormat);
+        }
+        let salt_len = reader.u8()? as usize;
+        let salt = reader.take(salt_len)?.to_vec();
+        let key_check = reader.array()?;
+        let nonce = reader.array()?;
+        Ok(Self { version, kdf, salt, key_check, nonce })
+    }
+}
+
+struct Reader<'a> {
+    buffer: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buffer: &'a [u8]) -> Self {
+        Self { buffer, position: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], StoreError> {
+        let bytes = self.buffer.get(self.position..self.position + len).ok_or(StoreError::Integrity)?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], StoreError> {
+        self.take(N).map(|bytes| bytes.try_into().unwrap_or([0; N]))
+    }
+
+    fn u8(&mut self) -> Result<u8, StoreError> {
+        self.array::<1>().map(|[byte]| byte)
+    }
+
+    fn u32(&mut self) -> Result<u32, StoreError> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64, StoreError> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn string(&mut self) -> Result<String, StoreError> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| StoreError::Integrity)
+    }
+}
+
+struct Pkcs7;
+
+impl Pkcs7 {
+    fn unpad(block: &[u8]) -> Result<&[u8], StoreError> {
+        let n = block.last().ok_or(StoreError::UnpadError)?;
+        if block.len() > u8::MAX as usize || *n == 0 || *n as usize >= block.len() {
+            Ok(block)
+        }
+        else {
+            let s = block.len() - *n as usize;
+            if block[s..].iter().any(|&v| v != *n) {
+                Ok(block)
+            }
+            else {
+                Ok(&block[..s])
+            }
+        }
+    }
+}
+
+#[test]
+fn unpad_empty_block() {
+    let arr: &[u8] = &[];
+    let res = Pkcs7::unpad(arr);
+    assert!(res.is_err());
+}
+
+#[test]
+fn unpad_block_with_length_greater_than_255() {
+    let arr = [2u8; 500];
+    let res = Pkcs7::unpad(&arr[..]);
+    assert!(res.is_ok_and(|unpadded_arr| unpadded_arr == arr));
+}
+
+#[test]
+fn unpad_block_with_last_byte_0() {
+    let arr = (0..100).rev().collect::<Vec<u8>>();
+    let res = Pkcs7::unpad(arr.as_slice());
+    assert!(res.is_ok_and(|unpadded_arr| unpadded_arr == arr));
+}
+
+#[test]
+fn unpad_block_with_last_byte_greater_than_length_of_array() {
+    let arr = [50u8, 51, 52];
+    let res = Pkcs7::unpad(&arr[..]);
+    assert!(res.is_ok_and(|unpadded_arr| unpadded_arr == &arr[..]));
+}
+
+#[test]
+fn unpad_block_with_bytes_ne_to_unpad_length() {
+    let arr = [9, 8, 7, 6, 5, 4, 3, 10, 10, 10, 10, 10, 10, 3, 10, 10, 10];
+    let res = Pkcs7::unpad(&arr[..]);
+    assert!(res.is_ok_and(|unpadded_arr| unpadded_arr == &arr[..]));
+}
+
+#[test]
+fn unpad_valid_block() {
+    let arr = [42, 35, 23, 187, 34, 69, 248, 21, 231, 126, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12];
+    let res = Pkcs7::unpad(&arr[..]);
+    let expected = [42, 35, 23, 187, 34, 69, 248, 21, 231, 126];
+    assert!(res.is_ok_and(|unpadded_arr| unpadded_arr == expected));
+}
+
+#[cfg(test)]
+fn test_storage(key: [u8; 32], name: &str) -> Arc<Storage> {
+    Arc::new(Storage::new_from_key(&key, Kdf::default(), vec!{7u8; 16}, std::env::temp_dir().join(format!("{}-{}", TITLE, name))))
+}
+
+#[cfg(test)]
+const ID_LEN: usize = 16;
+
+/// Encoded size of the folder and timestamps of a credential without folder.
+#[cfg(test)]
+const BOOKKEEPING_LEN: usize = 4 + 3 * 8;
+
+/// Encoded size of the details of a credential without any.
+#[cfg(test)]
+const DETAILS_LEN: usize = 4 + 3 * 4;
+
+/// `decoded` with the ids of `creds`, as credentials of vaults without ids get new ones.
+#[cfg(test)]
+fn with_ids(mut decoded: Vec<Credential>, creds: &[Credential]) -> Vec<Credential> {
+    decoded.iter_mut().zip(creds).for_each(|(decoded, cred)| decoded.id = cred.id);
+    decoded
+}
+
+#[cfg(test)]
+fn test_creds() -> Vec<Credential> {
+    vec!{ Credential::new("user".to_string(), Secret::Password("pass:word".to_string()), "some description".to_string()) }
+}
+
+#[test]
+fn generated_secrets() {
+    let policy = GeneratorPolicy::default();
+    let password = Secret::generate(SecretKind::Password, &policy).expect("error generating password");
+    assert_eq!(password.kind(), SecretKind::Password);
+    assert_eq!(password.value().len(), policy.length);
+    assert_ne!(Some(password), Secret::generate(SecretKind::Password, &policy));
+
+    let pin = Secret::generate(SecretKind::Pin, &policy).expect("error generating pin");
+    assert_eq!(pin.kind(), SecretKind::Pin);
+    assert!(pin.is_valid());
+    assert!(["", "0123", "12a", "99999999999"].iter().all(|value| !Secret::Pin(value.to_string()).is_valid()));
+    assert!(!Secret::Password(String::new()).is_valid());
+    assert_eq!(pin.value().len(), GENERATED_PIN_LEN as usize);
+    assert!(pin.value().parse::<u32>().is_ok_and(|pin| pin.to_string().len() == GENERATED_PIN_LEN as usize));
+}
+
+#[test]
+fn strength_estimate() {
+    let empty = Strength::estimate("");
+    assert!(empty.score == 0 && empty.hints.is_empty());
+
+    for (secret, hint) in [
+        ("P@ssw0rd2024!", StrengthHint::CommonPassword),
+        ("qwertyuiop", StrengthHint::CommonPassword),
+        ("zxcvbnm,./Ab", StrengthHint::KeyboardPattern),
+        ("abcdefghijk9", StrengthHint::Sequence),
+        ("aaaaaaaaaaaaaaaa", StrengthHint::Repeated),
+        ("Xk9q1987", StrengthHint::Year),
+    ] {
+        let strength = Strength::estimate(secret);
+        assert!(strength.hints.contains(&hint), "{} should hint {:?}, got {:?}", secret, hint, strength.hints);
+        assert!(strength.score <= 1, "{} should be weak, got {}", secret, strength.score);
+    }
+    assert_eq!(Strength::estimate("x7#Q").hints, vec!{ StrengthHint::Short });
+
+    let strong = Strength::estimate("vT9#qL2$wX8!mR4z");
+    assert!(strong.hints.is_empty());
+    assert_eq!(strong.score, Strength::MAX_SCORE);
+    let password = Secret::generate(SecretKind::Password, &GeneratorPolicy::default()).expect("error generating password");
+    assert!(Strength::estimate(password.value()).score >= 3);
+}
+
+#[test]
+fn fetch_with_wrong_secret() {
+    let storage = test_storage([1u8; 32], "wrong-secret");
+    futures_executor::block_on(save(Arc::clone(&storage), test_creds())).expect("error saving vault");
+
+    let res = futures_executor::block_on(fetch(Arc::clone(&storage)));
+    assert!(res.is_ok_and(|creds| creds.len() == 1 && creds[0].secret.value() == "pass:word"));
+    let res = futures_executor::block_on(fetch(test_storage([2u8; 32], "wrong-secret")));
+    assert!(matches!(res, Err(StoreError::InvalidSecret)));
+    fs::remove_dir_all(&storage.directory).expect("error cleaning up directory");
+}
+
+#[test]
+fn fetch_tampered_vault() {
+    let storage = test_storage([3u8; 32], "tampered");
+    futures_executor::block_on(save(Arc::clone(&storage), test_creds())).expect("error saving vault");
+    let path = storage.path();
+    let mut buffer = fs::read(&path).expect("error reading vault");
+
+    let last = buffer.len() - 1;
+    buffer[last] ^= 1;
+    fs::write(&path, &buffer).expect("error writing vault");
+    let res = futures_executor::block_on(fetch(Arc::clone(&storage)));
+    assert!(matches!(res, Err(StoreError::Integrity)));
+
+    fs::write(&path, &buffer[..MAGIC.len() + 8]).expect("error writing vault");
+    let res = futures_executor::block_on(fetch(Arc::clone(&storage)));
+    assert!(matches!(res, Err(StoreError::Integrity)));
+    fs::remove_dir_all(&storage.directory).expect("error cleaning up directory");
+}
+
+#[test]
+fn nonce_changes_on_every_save() {
+    let storage = test_storage([4u8; 32], "nonce");
+    let path = storage.path();
+    futures_executor::block_on(save(Arc::clone(&storage), test_creds())).expect("error saving vault");
+    let first = fs::read(&path).expect("error reading vault");
+    futures_executor::block_on(save(Arc::clone(&storage), test_creds())).expect("error saving vault");
+    let second = fs::read(&path).expect("error reading vault");
+    assert_ne!(first, second);
+    fs::remove_dir_all(&storage.directory).expect("error cleaning up directory");
+}
+
+#[test]
+fn records_with_separators() {
+    let storage = test_storage([6u8; 32], "separators");
+    let creds = vec!{
+        Credential::new("user,name".to_string(), Secret::Password("pa,ss:wo\nrd".to_string()), "line one\nline two, with comma".to_string()),
+        Credential::new(String::new(), Secret::Pin("1234".to_string()), String::new()),
+    };
+    futures_executor::block_on(save(Arc::clone(&storage), creds.clone())).expect("error saving vault");
+    let fetched = futures_executor::block_on(fetch(Arc::clone(&storage))).expect("error reading vault");
+    assert_eq!(fetched, creds);
+    fs::remove_dir_all(&storage.directory).expect("error cleaning up directory");
+}
+
+#[test]
+fn truncated_records() {
+    let buffer = encode_records(test_creds().iter());
+    assert!(matches!(decode_records(&buffer[..buffer.len() - 1], FORMAT_VERSION), Err(StoreError::Integrity)));
+    let mut buffer = buffer;
+    buffer.push(0);
+    assert!(matches!(decode_records(&buffer, FORMAT_VERSION), Err(StoreError::Integrity)));
+}
+
+#[test]
+fn records_without_policy() {
+    let creds = test_creds();
+    let mut buffer = encode_records(creds.iter()).to_vec();
+    buffer.truncate(buffer.len() - ID_LEN - BOOKKEEPING_LEN - DETAILS_LEN);
+    assert_eq!(buffer.pop(), Some(0));
+    assert_eq!(with_ids(decode_records(&buffer, BINARY_FORMAT_VERSION).expect("error decoding records"), &creds), creds);
+}
+
+#[test]
+fn records_without_details() {
+    let mut creds = test_creds();
+    creds[0].policy = Some(GeneratorPolicy::default());
+    let mut buffer = encode_records(creds.iter()).to_vec();
+    buffer.truncate(buffer.len() - ID_LEN - BOOKKEEPING_LEN - DETAILS_LEN);
+    assert_eq!(with_ids(decode_records(&buffer, PLAIN_RECORD_FORMAT_VERSION).expect("error decoding records"), &creds), creds);
+
+    creds[0].fields.push(CustomField { name: "recovery".to_string(), kind: FieldKind::Hidden, value: "code".to_string() });
+    let mut buffer = encode_records(creds.iter()).to_vec();
+    let kind = buffer.len() - ID_LEN - BOOKKEEPING_LEN - "code".len() - 4 - 1;
+    buffer[kind] = FieldKind::ALL.len() as u8;
+    assert!(matches!(decode_records(&buffer, FORMAT_VERSION), Err(StoreError::Integrity)));
+    buffer[kind] = FIELD_TOTP;
+    assert!(matches!(decode_records(&buffer, ID_FORMAT_VERSION), Err(StoreError::Integrity)));
+    assert!(decode_records(&buffer, FORMAT_VERSION).is_ok_and(|decoded| decoded[0].fields[0].kind == FieldKind::Totp));
+}
+
+#[test]
+fn totp_codes() {
+    let key = |secret: &str| format!("otpauth://totp/pine:user?secret={}&digits=8&algorithm=", secret);
+    let sha1 = Totp::parse(&(key("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ") + "SHA1")).expect("error parsing uri");
+    let sha256 = Totp::parse(&(key("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA") + "SHA256")).expect("error parsing uri");
+    let sha512 = Totp::parse(&(key("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNA") + "SHA512")).expect("error parsing uri");
+    for (time, codes) in [(59, ["94287082", "46119246", "90693936"]), (1111111109, ["07081804", "68084774", "25091201"]), (20000000000, ["65353130", "77737706", "47863826"])] {
+        assert_eq!([&sha1, &sha256, &sha512].map(|totp| totp.code(time).expect("error computing code")), codes);
+    }
+    assert_eq!(sha1.seconds_left(59), 1);
+
+    let bare = Totp::parse("gezd gnbv-gy3t qojq gezd gnbv-gy3t qojq").expect("error parsing key");
+    assert_eq!((bare.algorithm, bare.digits, bare.period), (TotpAlgorithm::Sha1, 6, 30));
+    assert_eq!(bare.code(59).as_deref(), Some("287082"));
+    let custom = Totp::parse("OTPAUTH://TOTP/x?period=60&secret=GEZDGNBV").expect("error parsing uri");
+    assert_eq!((custom.period, custom.seconds_left(61)), (60, 59));
+    for invalid in ["", "otpauth://hotp/x?secret=GEZDGNBV", "otpauth://totp/x?secret=GEZDGNBV&digits=9", "otpauth://totp/x?secret=GEZDGNBV&period=0", "otpauth://totp/x", "GEZDGNB1"] {
+        assert!(Totp::parse(invalid).is_none(), "{} should be invalid", invalid);
+    }
+}
+
+#[test]
+fn records_without_bookkeeping() {
+    let mut creds = test_creds();
+    creds[0].title = "title".to_string();
+    let mut buffer = encode_records(creds.iter()).to_vec();
+    buffer.truncate(buffer.len() - ID_LEN - BOOKKEEPING_LEN);
+    assert_eq!(with_ids(decode_records(&buffer, DETAILS_FORMAT_VERSION).expect("error decoding records"), &creds), creds);
+
+    creds[0].folder = "folder".to_string();
+    creds[0].created = Credential::now();
+    let decoded = decode_records(&encode_records(creds.iter()), FORMAT_VERSION).expect("error decoding records");
+    assert_eq!(decoded, creds);
+}
+
+#[test]
+fn records_without_ids() {
+    let mut creds = test_creds();
+    creds.push(Credential::new("other".to_string(), Secret::Pin("1234".to_string()), String::new()));
+    let mut buffer = 2u32.to_le_bytes().to_vec();
+    for cred in &creds {
+        let record = encode_records(std::iter::once(cred));
+        buffer.extend_from_slice(&record[4..record.len() - ID_LEN]);
+    }
+    let decoded = decode_records(&buffer, BOOKKEEPING_FORMAT_VERSION).expect("error decoding records");
+    assert_eq!(with_ids(decoded.clone(), &creds), creds);
+    assert!(decoded[0].id != decoded[1].id && decoded.iter().zip(&creds).all(|(decoded, cred)| decoded.id != cred.id));
+
+    let id = CredentialId([0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0x4d, 0xef, 0x80, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07]).to_string();
+    assert_eq!(id, "12345678-9abc-4def-8001-020304050607");
+    assert_eq!(CredentialId::parse(&id).map(|id| id.to_string()), Some(id));
+    assert!(CredentialId::parse("12345678-9abc-4def-8001-0203040506").is_none());
+    let random = CredentialId::random();
+    assert_eq!((random.0[6] >> 4, random.0[8] >> 6), (4, 2));
+}
+
+#[test]
+fn generator_policy() {
+    let policy = GeneratorPolicy { length: 64, symbols: false, exclude_ambiguous: true, ..Default::default() };
+    assert_eq!(policy.entropy(SecretKind::Password), 64f64 * 57f64.log2());
+    for _ in 0..32 {
+        let password = policy.generate().expect("error generating password");
+        assert_eq!(password.chars().count(), 64);
+        assert!(password.chars().all(|c| c.is_ascii_alphanumeric() && !GeneratorPolicy::AMBIGUOUS.contains(c)));
+        assert!(password.chars().any(|c| c.is_ascii_uppercase()) && password.chars().any(|c| c.is_ascii_lowercase()) && password.chars().any(|c| c.is_ascii_digit()));
+    }
+
+    let policy = GeneratorPolicy { length: 3, uppercase: false, lowercase: false, digits: false, symbol_set: "€ € ß".to_string(), ..Default::default() };
+    assert!(policy.generate().is_some_and(|password| password.chars().count() == 3 && password.chars().all(|c| c == '€' || c == 'ß')));
+    assert!(GeneratorPolicy { length: 1, digits: true, ..policy.clone() }.generate().is_none());
+    assert!(GeneratorPolicy { symbol_set: " ".to_string(), ..policy.clone() }.generate().is_none());
+    assert!(GeneratorPolicy { symbols: false, ..policy }.generate().is_none());
+}
+
+#[test]
+fn passphrase_generation() {
+    let policy = GeneratorPolicy { words: 5, separator: " ".to_string(), capitalize: true, append_digit: true, ..Default::default() };
+    let passphrase = Secret::generate(SecretKind::Passphrase, &policy).expect("error generating passphrase");
+    assert_eq!(passphrase.kind(), SecretKind::Passphrase);
+    let (words, digit) = passphrase.value().split_at(passphrase.value().len() - 1);
+    assert!(digit.chars().all(|c| c.is_ascii_digit()));
+    let words = words.split(' ').collect::<Vec<&str>>();
+    assert_eq!(words.len(), 5);
+    assert!(words.iter().all(|word| word.starts_with(|c: char| c.is_uppercase()) && GeneratorPolicy::wordlist().contains(&word.to_lowercase().as_str())));
+    assert_eq!(GeneratorPolicy::wordlist().len(), 7776);
+    assert_eq!(policy.entropy(SecretKind::Passphrase), 5f64 * (GeneratorPolicy::wordlist().len() as f64).log2() + 10f64.log2());
+    assert!(Secret::generate(SecretKind::Passphrase, &GeneratorPolicy { words: 0, ..policy.clone() }).is_none());
+
+    let mut buffer = Vec::new();
+    policy.encode(&mut buffer);
+    buffer.truncate(buffer.len() - (4 + 1 + 4 + policy.separator.len()));
+    let decoded = GeneratorPolicy::decode(&mut Reader::new(&buffer), CHARACTER_POLICY_FORMAT_VERSION).expect("error decoding policy");
+    assert_eq!(decoded, GeneratorPolicy { words: 6, separator: "-".to_string(), capitalize: false, append_digit: false, ..policy });
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn records_round_trip(records in proptest::collection::vec((".*", 0usize..3, ".*", ".*", proptest::option::of((0..=GeneratorPolicy::MAX_LENGTH, proptest::array::uniform8(proptest::bool::ANY), ".*", 0..=GeneratorPolicy::MAX_WORDS, ".*")), (".*", proptest::collection::vec(".*", 0..4), proptest::collection::vec(".*", 0..4), proptest::collection::vec((".*", 0usize..5, ".*"), 0..4)), (".*", proptest::array::uniform3(proptest::num::u64::ANY), proptest::array::uniform16(proptest::num::u8::ANY))), 0..16)) {
+        let creds = records.into_iter().map(|(username, kind, secret, description, policy, (title, urls, tags, fields), (folder, [created, modified, used], id))| {
+            let policy = policy.map(|(length, [uppercase, lowercase, digits, symbols, exclude_ambiguous, require_each, capitalize, append_digit], symbol_set, words, separator)| {
+                GeneratorPolicy { length, uppercase, lowercase, digits, symbols, exclude_ambiguous, require_each, symbol_set, words, separator, capitalize, append_digit }
+            });
+            let kind = [SecretKind::Password, SecretKind::Passphrase, SecretKind::Pin][kind];
+            let fields = fields.into_iter().map(|(name, kind, value)| CustomField { name, kind: FieldKind::ALL[kind], value }).collect();
+            Credential { id: CredentialId(id), title, username, secret: Secret::new(kind, secret), description, folder, urls, tags, fields, policy, created, modified, used }
+        }).collect::<Vec<_>>();
+        let buffer = encode_records(creds.iter());
+        proptest::prop_assert_eq!(buffer.len(), 4 + creds.iter().map(record_len).sum::<usize>());
+        let decoded = decode_records(&buffer, FORMAT_VERSION).expect("error decoding records");
+        proptest::prop_assert_eq!(decoded, creds);
+    }
+}
+
+#[test]
+fn backup_rotation() {
+    let storage = test_storage([9u8; 32], "backups");
+    Config { backups: 2, ..Default::default() }.write_to(&storage.directory).expect("error writing config");
+    let mut saved = Vec::new();
+    for i in 0..4 {
+        let creds = vec!{ Credential::new(format!("user{}", i), Secret::Pin(i.to_string()), String::new()) };
+        futures_executor::block_on(save(Arc::clone(&storage), creds)).expect("error saving vault");
+        saved.push(fs::read(storage.path()).expect("error reading vault"));
+    }
+    assert!(fs::read_dir(&storage.directory).expect("error listing directory").all(|entry| entry.is_ok_and(|entry| !entry.file_name().to_string_lossy().ends_with(".tmp"))));
+    assert_eq!(storage.backups().len(), 2);
+    assert_eq!(fs::read(storage.backup_path(1)).expect("error reading backup"), saved[2]);
+    assert_eq!(fs::read(storage.backup_path(2)).expect("error reading backup"), saved[1]);
+
+    Config { backups: 1, ..Default::default() }.write_to(&storage.directory).expect("error writing config");
+    futures_executor::block_on(save(Arc::clone(&storage), test_creds())).expect("error saving vault");
+    assert_eq!(storage.backups().len(), 1);
+    assert_eq!(fs::read(storage.backup_path(1)).expect("error reading backup"), saved[3]);
+
+    futures_executor::block_on(touch(Arc::clone(&storage), test_creds()[0].id, 1)).expect("error recording use");
+    assert_eq!(storage.backups().len(), 1);
+    assert_eq!(fs::read(storage.backup_path(1)).expect("error reading backup"), saved[3]);
+    fs::remove_dir_all(&storage.directory).expect("error cleaning up directory");
+}
+
+#[test]
+fn restore_backup() {
+    let directory = std::env::temp_dir().join(format!("{}-restore", TITLE));
+    let kdf = Kdf::Argon2id { memory_kib: 64, iterations: 1, parallelism: 1 };
+    let salt = vec!{4u8; SALT_LEN};
+    let key = kdf.derive("my_secret", &salt).expect("error deriving key");
+    let storage = Arc::new(Storage::new_from_key(&key, kdf, salt, directory));
+    futures_executor::block_on(save(Arc::clone(&storage), test_creds())).expect("error saving vault");
+    futures_executor::block_on(save(Arc::clone(&storage), Vec::new())).expect("error saving vault");
+
+    let res = futures_executor::block_on(restore(Arc::clone(&storage), "wrong_secret".to_string(), 1));
+    assert!(matches!(res, Err(StoreError::InvalidSecret)));
+    let creds = futures_executor::block_on(restore(Arc::clone(&storage), "my_secret".to_string(), 1)).expect("error restoring backup");
+    assert!(creds.len() == 1 && creds[0].secret.value() == "pass:word");
+    let creds = futures_executor::block_on(fetch(Arc::clone(&storage))).expect("error reading vault");
+    assert_eq!(creds.len(), 1);
+    let creds = futures_executor::block_on(restore(Arc::clone(&storage), "my_secret".to_string(), 1)).expect("error restoring backup");
+    assert!(creds.is_empty());
+    fs::remove_dir_all(&storage.directory).expect("error cleaning up directory");
+}
+
+#[test]
+fn config_round_trip() {
+    let directory = std::env::temp_dir().join(format!("{}-config", TITLE));
+    assert_eq!(Config::load_from(&directory), Config::default());
+    let config = Config { backups: 7, sort: SortMode::RecentlyUsed, grouping: Grouping::Folder, reveal_seconds: 30, clipboard_seconds: 45, idle_lock_minutes: 0, lock_on_minimize: false };
+    config.write_to(&directory).expect("error writing config");
+    assert_eq!(Config::load_from(&directory), config);
+    fs::write(directory.join(Config::FILE_NAME), "backups = many\nsort = size\nreveal_seconds = 0\nclipboard_seconds = -1\nidle_lock_minutes = soon\nlock_on_minimize = yes\nunknown = 1\n").expect("error writing config");
+    assert_eq!(Config::load_from(&directory), Config::default());
+    fs::write(directory.join(Config::FILE_NAME), "reveal_seconds = 3601\nclipboard_seconds = 18446744073709551615\n").expect("error writing config");
+    assert_eq!(Config::load_from(&directory), Config::default());
+    fs::remove_dir_all(&directory).expect("error cleaning up directory");
+}
+
+#[test]
+fn header_round_trip() {
+    let header = Header { version: FORMAT_VERSION, kdf: Kdf::Pbkdf2 { iterations: 42 }, salt: vec!{1, 2, 3}, key_check: [4; KEY_CHECK_LEN], nonce: [5; NONCE_LEN] };
+    let buffer = header.encode();
+    let mut reader = Reader::new(&buffer);
+    let decoded = Header::decode(&mut reader).expect("error decoding header");
+    assert_eq!(reader.position, buffer.len());
+    assert_eq!(decoded.version, header.version);
+    assert_eq!(decoded.kdf, header.kdf);
+    assert_eq!(decoded.salt, header.salt);
+    assert_eq!(decoded.key_check, header.key_check);
+    assert_eq!(decoded.nonce, header.nonce);
+}
+
+#[test]
+fn header_with_unknown_version() {
+    let mut buffer = Header { version: FORMAT_VERSION, kdf: Kdf::default(), salt: Vec::new(), key_check: [0; KEY_CHECK_LEN], nonce: [0; NONCE_LEN] }.encode();
+    buffer[MAGIC.len()] = FORMAT_VERSION + 1;
+    assert!(matches!(Header::decode(&mut Reader::new(&buffer)), Err(StoreError::UnsupportedFormat)));
+}
+
+#[cfg(test)]
+fn write_legacy_vault(storage: &Storage, content: &str) {
+    use aes::cipher::BlockEncrypt;
+
+    let mut buffer = Vec::new();
+    for chunk in content.as_bytes().chunks(16) {
+        let n = 16 - chunk.len();
+        let mut block = [n as u8; 16];
+        block[..chunk.len()].copy_from_slice(chunk);
+        let mut block_array = GenericArray::from(block);
+        storage.legacy_cipher.encrypt_block(&mut block_array);
+        buffer.extend_from_slice(block_array.as_slice());
+    }
+    fs::create_dir_all(&storage.directory).expect("error creating directory");
+    fs::write(storage.path(), buffer).expect("error writing vault");
+}
+
+#[test]
+fn legacy_vault_migration() {
+    let storage = test_storage([5u8; 32], "legacy");
+    write_legacy_vault(&storage, "user,password:pass:word,some description");
+    assert!(storage.is_legacy());
+
+    let creds = futures_executor::block_on(fetch(Arc::clone(&storage))).expect("error reading legacy vault");
+    assert!(creds.len() == 1 && creds[0].username == "user" && creds[0].secret.value() == "pass:word" && creds[0].description == "some description");
+    futures_executor::block_on(save(Arc::clone(&storage), creds)).expect("error saving vault");
+    assert!(!storage.is_legacy());
+
+    let creds = futures_executor::block_on(fetch(Arc::clone(&storage))).expect("error reading migrated vault");
+    assert!(creds.len() == 1 && creds[0].secret.value() == "pass:word");
+    fs::remove_dir_all(&storage.directory).expect("error cleaning up directory");
+}
+
+#[test]
+fn argon2id_derivation() {
+    let kdf = Kdf::Argon2id { memory_kib: 64, iterations: 1, parallelism: 1 };
+    let key = kdf.derive("my_secret", &[1u8; SALT_LEN]).expect("error deriving key");
+    assert_eq!(key, kdf.derive("my_secret", &[1u8; SALT_LEN]).expect("error deriving key"));
+    assert_ne!(key, kdf.derive("my_secret", &[2u8; SALT_LEN]).expect("error deriving key"));
+    assert_ne!(key, Kdf::Argon2id { memory_kib: 128, iterations: 1, parallelism: 1 }.derive("my_secret", &[1u8; SALT_LEN]).expect("error deriving key"));
+    assert!(matches!(Kdf::Argon2id { memory_kib: 0, iterations: 1, parallelism: 1 }.derive("my_secret", &[1u8; SALT_LEN]), Err(StoreError::Kdf(_))));
+}
+
+#[test]
+fn kdf_bounds() {
+    assert!(Kdf::default().is_valid() && Kdf::LEGACY.is_valid());
+    for kdf in [Kdf::Pbkdf2 { iterations: 0 }, Kdf::Argon2id { memory_kib: u32::MAX, iterations: 3, parallelism: 1 }, Kdf::Argon2id { memory_kib: 64, iterations: u32::MAX, parallelism: 1 }, Kdf::Argon2id { memory_kib: 64, iterations: 1, parallelism: 0 }] {
+        let header = Header { version: FORMAT_VERSION, kdf, salt: vec!{1, 2, 3}, key_check: [4; KEY_CHECK_LEN], nonce: [5; NONCE_LEN] };
+        assert!(matches!(Header::decode(&mut Reader::new(&header.encode())), Err(StoreError::KdfBounds)));
+    }
+    let storage = test_storage([6u8; 32], "kdf-bounds");
+    let kdf = Kdf::Argon2id { memory_kib: u32::MAX, iterations: 3, parallelism: 1 };
+    assert!(matches!(futures_executor::block_on(rekey(storage, "my_secret".to_string(), kdf)), Err(StoreError::KdfBounds)));
+}
+
+#[test]
+fn concurrent_saves() {
+    let storage = test_storage([4u8; 32], "concurrent-saves");
+    let writers = (0..8).map(|i| {
+        let storage = Arc::clone(&storage);
+        std::thread::spawn(move || (0..8).try_for_each(|j| {
+            let mut creds = test_creds();
+            creds[0].title = format!("{} {}", i, j);
+            match j % 2 {
+                0 => futures_executor::block_on(save(Arc::clone(&storage), creds)),
+                _ => futures_executor::block_on(touch(Arc::clone(&storage), creds[0].id, j)),
+            }
+        }))
+    }).collect::<Vec<_>>();
+    for writer in writers {
+        writer.join().expect("error joining writer").expect("error saving vault");
+    }
+    let creds = futures_executor::block_on(fetch(Arc::clone(&storage))).expect("error reading vault");
+    assert!(creds.len() == 1 && creds[0].secret.value() == "pass:word");
+    assert!(fs::read_dir(&storage.directory).expect("error listing directory").all(|entry| entry.is_ok_and(|entry| !entry.file_name().to_string_lossy().ends_with(".tmp"))));
+    assert_ne!(temp_path(&storage.path()), temp_path(&storage.path()));
+    fs::remove_dir_all(&storage.directory).expect("error cleaning up directory");
+}
+
+#[test]
+fn usage_outside_vault() {
+    let storage = test_storage([6u8; 32], "usage");
+    let mut creds = test_creds();
+    creds.push(Credential::new("other".to_string(), Secret::Pin("1234".to_string()), String::new()));
+    futures_executor::block_on(save(Arc::clone(&storage), creds.clone())).expect("error saving vault");
+    let vault = fs::read(storage.path()).expect("error reading vault");
+    futures_executor::block_on(touch(Arc::clone(&storage), creds[1].id, 42)).expect("error recording use");
+    assert_eq!(fs::read(storage.path()).expect("error reading vault"), vault);
+    let fetched = futures_executor::block_on(fetch(Arc::clone(&storage))).expect("error reading vault");
+    assert_eq!(fetched.iter().map(|cred| cred.used).collect::<Vec<u64>>(), [0, 42]);
+
+    futures_executor::block_on(save(Arc::clone(&storage), creds[..1].to_vec())).expect("error saving vault");
+    assert!(storage.read_usage().is_empty());
+    fs::remove_dir_all(&storage.directory).expect("error cleaning up directory");
+}
+
+#[test]
+fn saves_out_of_order() {
+    let storage = test_storage([5u8; 32], "saves-out-of-order");
+    let older = Records::new(test_creds().iter());
+    let newer = Records::new(Vec::<Credential>::new().iter());
+    futures_executor::block_on(save(Arc::clone(&storage), newer)).expect("error saving vault");
+    futures_executor::block_on(save(Arc::clone(&storage), older)).expect("error saving vault");
+    assert!(futures_executor::block_on(fetch(Arc::clone(&storage))).expect("error reading vault").is_empty());
+    fs::remove_dir_all(&storage.directory).expect("error cleaning up directory");
+}
+
+#[test]
+fn rekey_vault() {
+    let salt = vec!{3u8; SALT_LEN};
+    let directory = std::env::temp_dir().join(format!("{}-rekey", TITLE));
+    let key = Kdf::LEGACY.derive("my_secret", &salt).expect("error deriving key");
+    let storage = Arc::new(Storage::new_from_key(&key, Kdf::LEGACY, salt, directory));
+    futures_executor::block_on(save(Arc::clone(&storage), test_creds())).expect("error saving vault");
+
+    let kdf = Kdf::Argon2id { memory_kib: 64, iterations: 1, parallelism: 1 };
+    let res = futures_executor::block_on(rekey(Arc::clone(&storage), "wrong_secret".to_string(), kdf));
+    assert!(matches!(res, Err(StoreError::InvalidSecret)));
+    let rekeyed = futures_executor::block_on(rekey(Arc::clone(&storage), "my_secret".to_string(), kdf)).expect("error re-keying vault");
+    assert_eq!(rekeyed.kdf(), kdf);
+
+    let buffer = fs::read(rekeyed.path()).expect("error reading vault");
+    let header = Header::decode(&mut Reader::new(&buffer)).expect("error decoding header");
+    assert_eq!(header.kdf, kdf);
+    let creds = futures_executor::block_on(fetch(Arc::clone(&rekeyed))).expect("error reading re-keyed vault");
+    assert!(creds.len() == 1 && creds[0].secret.value() == "pass:word");
+    assert!(matches!(futures_executor::block_on(fetch(Arc::clone(&storage))), Err(StoreError::InvalidSecret)));
+    fs::remove_dir_all(&storage.directory).expect("error cleaning up directory");
+}
+
+#[test]
+fn change_master_secret() {
+    let salt = vec!{5u8; SALT_LEN};
+    let directory = std::env::temp_dir().join(format!("{}-change-secret", TITLE));
+    let kdf = Kdf::Argon2id { memory_kib: 64, iterations: 1, parallelism: 1 };
+    let key = kdf.derive("old_secret", &salt).expect("error deriving key");
+    let storage = Arc::new(Storage::new_from_key(&key, kdf, salt.clone(), directory.clone()));
+    futures_executor::block_on(save(Arc::clone(&storage), test_creds())).expect("error saving vault");
+
+    let res = futures_executor::block_on(change_secret(Arc::clone(&storage), "wrong_secret".to_string(), "new_secret".to_string(), kdf));
+    assert!(matches!(res, Err(StoreError::InvalidSecret)));
+    let mut creds = test_creds();
+    creds[0].title = "newer".to_string();
+    futures_executor::block_on(save(Arc::clone(&storage), creds)).expect("error saving vault");
+    let backup = storage.backup_path(1);
+    let old_backup = fs::read(&backup).expect("error reading backup");
+    let (changed, deleted) = futures_executor::block_on(change_secret(Arc::clone(&storage), "old_secret".to_string(), "new_secret".to_string(), kdf)).expect("error changing secret");
+    assert_ne!(changed.salt, salt);
+    assert_eq!((changed.backups().len(), deleted), (2, 0));
+    assert_ne!(fs::read(storage.backup_path(2)).expect("error reading backup"), old_backup);
+    let restored = futures_executor::block_on(restore(Arc::clone(&changed), "new_secret".to_string(), 2)).expect("error restoring backup");
+    assert!(restored.len() == 1 && restored[0].title.is_empty());
+    assert!(matches!(futures_executor::block_on(restore(Arc::clone(&changed), "old_secret".to_string(), 1)), Err(StoreError::InvalidSecret)));
+
+    fs::write(&backup, old_backup).expect("error writing backup");
+    let (changed, deleted) = futures_executor::block_on(change_secret(Arc::clone(&changed), "new_secret".to_string(), "newest_secret".to_string(), kdf)).expect("error changing secret");
+    assert_eq!((changed.backups().len(), deleted), (2, 1));
+    let restored = futures_executor::block_on(restore(Arc::clone(&changed), "newest_secret".to_string(), 2)).expect("error restoring backup");
+    assert!(restored.len() == 1 && restored[0].title == "newer");
+    assert!(matches!(futures_executor::block_on(save(Arc::clone(&storage), Vec::new())), Err(StoreError::Rekeyed)));
+
+    let (_, creds) = futures_executor::block_on(open("newest_secret".to_string(), directory.clone())).expect("error opening vault with new secret");
+    assert!(creds.len() == 1 && creds[0].secret.value() == "pass:word");
+    assert!(matches!(futures_executor::block_on(open("old_secret".to_string(), directory.clone())), Err(StoreError::InvalidSecret)));
+    fs::remove_dir_all(&directory).expect("error cleaning up directory");
+}
+
+#[test]
+fn salt_creation() {
+    let directory = std::env::temp_dir().join(format!("{}-salt-creation", TITLE));
+    fs::create_dir_all(&directory).expect("error creating directory");
+    let storage = Storage::open("my_secret", directory.clone()).expect("error opening storage");
+    assert_eq!(storage.salt.len(), SALT_LEN);
+    assert!(Storage::legacy_salt_files(&directory).is_empty());
+    let other_storage = Storage::open("my_secret", directory.clone()).expect("error opening storage");
+    assert_ne!(storage.salt, other_storage.salt);
+    fs::remove_dir_all(&directory).expect("error cleaning up directory");
+}
+
+#[test]
+fn legacy_vault_without_salt() {
+    let storage = test_storage([8u8; 32], "missing-salt");
+    write_legacy_vault(&storage, "user,password:secret,");
+    assert!(matches!(Storage::open("my_secret", storage.directory.clone()), Err(StoreError::MissingSalt)));
+    fs::remove_dir_all(&storage.directory).expect("error cleaning up directory");
+}
+
+#[test]
+fn legacy_salt_decoys() {
+    let directory = std::env::temp_dir().join(format!("{}-salt-decoys", TITLE));
+    fs::create_dir_all(&directory).expect("error creating directory");
+    let salt = vec![3u8; SALT_LEN];
+    fs::write(directory.join("realsalt"), &salt).expect("error writing salt");
+    let key = Kdf::LEGACY.derive("my_secret", &salt).expect("error deriving key");
+    write_legacy_vault(&Storage::new_from_key(&key, Kdf::LEGACY, salt.clone(), directory.clone()), "user,pin:1234,");
+    std::thread::sleep(time::Duration::from_secs(1));
+    fs::write(directory.join("decoysalt"), [4u8; SALT_LEN]).expect("error writing salt");
+
+    let storage = Storage::open("my_secret", directory.clone()).expect("error opening legacy vault");
+    assert_eq!(storage.salt, salt);
+    assert!(Storage::open("wrong_secret", directory.clone()).is_err());
+    let (_, creds) = futures_executor::block_on(open("my_secret".to_string(), directory.clone())).expect("error opening legacy vault");
+    assert!(creds.len() == 1 && creds[0].secret.value() == "1234");
+    fs::remove_dir_all(&directory).expect("error cleaning up directory");
+}
+
+#[test]
+fn salt_update() {
+    use rand::Rng;
+
+    const SALT_EXTENTION: &str = "salt";
+    let directory = std::env::temp_dir().join(format!("{}-salt-update", TITLE));
+
+    if fs::create_dir_all(&directory).is_err() {
+        panic!("Failed to create directory");
+    }
+
+    let mut rng = rand::thread_rng();
+    let mut create_salt_file = || {
+        let file_name= {
+            let length = rng.gen_range(10 - SALT_EXTENTION.len()..=30 - SALT_EXTENTION.len());
+            let random = (0..length).map(|_| {
+                let choice = rng.gen_range(0u8..3);
+                match choice {
+                    0 => rng.gen_range('a'..='z'),
+                    1 => rng.gen_range('A'..='Z'),
+                    2 => rng.gen_range('0'..='9'),
+                    _ => unreachable!(),
+                }
+            }).collect::<String>();
+            format!("{}{}", random, SALT_EXTENTION)
+        };
+        let salt_len = rng.gen_range(5usize..u8::MAX as usize);
+        let mut salt = vec!{0u8; salt_len};
+        if rng.try_fill_bytes(&mut salt).is_err() {
+            panic!("error filling random bytes");
+        }
+        if fs::write(directory.join(file_name), salt.as_slice()).is_err() {
+            panic!("error writing to file");
+        }
+        salt
+    };
+
+    create_salt_file();
+    std::thread::sleep(time::Duration::from_secs(1));
+    create_salt_file();
+    std::thread::sleep(time::Duration::from_secs(1));
+    create_salt_file();
+    std::thread::sleep(time::Duration::from_secs(1));
+    let content = create_salt_file();
+
+    let key = Kdf::LEGACY.derive("my_secret", &content).expect("error deriving key");
+    write_legacy_vault(&Storage::new_from_key(&key, Kdf::LEGACY, content.clone(), directory.clone()), "user,pin:1234,");
+
+    let (storage, creds) = futures_executor::block_on(open("my_secret".to_string(), directory.clone())).expect("error opening legacy vault");
+    assert!(creds.len() == 1 && creds[0].secret.value() == "1234");
+    assert!(!storage.is_legacy());
+    assert!(Storage::legacy_salt_files(&directory).is_empty());
+
+    let buffer = fs::read(storage.path()).expect("error reading vault");
+    let header = Header::decode(&mut Reader::new(&buffer)).expect("error decoding header");
+    assert_eq!(header.salt, content);
+    let (_, creds) = futures_executor::block_on(open("my_secret".to_string(), directory.clone())).expect("error opening migrated vault");
+    assert!(creds.len() == 1 && creds[0].secret.value() == "1234");
+    fs::remove_dir_all(&directory).expect("error cleaning up directory");
+}
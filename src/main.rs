@@ -1,10 +1,25 @@
-use core::StoreError;
-use std::{fmt, sync::Arc, thread::sleep, time::Duration};
-use iced::{alignment, clipboard, executor, font::Weight, widget::{button, column, container, horizontal_space, keyed_column, radio, row, text, text_editor, text_input, Column, Container, Row}, window::{self, Position}, Alignment, Application, Command, Element, Font, Length, Pixels, Settings, Size};
+use pine_core::{self as core, SecretKind, StoreError};
+use std::{borrow::Cow, cmp::Ordering, fmt, sync::Arc, time::{Duration, Instant, SystemTime}};
+use iced::{alignment, clipboard, event, executor, font::Weight, keyboard, mouse, time, widget::{button, canvas, checkbox, column, container, horizontal_space, keyed_column, progress_bar, radio, row, scrollable, text, text_editor, text_input, Column, Container, Row, Space, Text}, window::{self, Position}, Alignment, Application, Command, Element, Event, Font, Length, Pixels, Radians, Rectangle, Renderer, Settings, Size, Subscription};
+use zeroize::{Zeroize, Zeroizing};
 
 const TITLE: &str = "pine";
+const SEARCH_INPUT: &str = "search";
+const CONTENT_SCROLLABLE: &str = "content";
+/// Estimated height of a row of the credential list, including the spacing below it. Rows away from
+/// the viewport are not built but stand in as empty space of this height.
+const ROW_HEIGHT: f32 = 130f32;
+/// Rows built above and below the viewport, so rows taller than estimated still fill it.
+const LIST_MARGIN: usize = 25;
+/// Ticks this far apart by the wall clock mean the system was suspended in between.
+const SUSPEND_GAP: Duration = Duration::from_secs(30);
 
 fn main() -> iced::Result {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    if !args.is_empty() {
+        std::process::exit(cli::run(args));
+    }
+
     let settings = Settings {
         window: window::Settings {
             size: Size::new(800f32, 800f32),
@@ -24,9 +39,21 @@ fn main() -> iced::Result {
 struct Pine {
     cred_list: Vec<Cred>,
     insert_mode: InsertMode,
+    lock_mode: LockMode,
+    settings_mode: SettingsMode,
     toasts: Vec<Toast>,
-    storage: Arc<core::Storage>,
-    fault: bool,
+    copied: Option<Copied>,
+    /// Last key press, click or scroll, to lock the vault once it has been idle for too long. Wall
+    /// clock times, as the monotonic clock stands still while the system is suspended on some platforms.
+    last_activity: SystemTime,
+    last_tick: SystemTime,
+    search: String,
+    listing: Listing,
+    /// Offset and height of the visible part of the list, as last scrolled.
+    viewport: (f32, f32),
+    /// The key, only while the vault is unlocked.
+    storage: Option<Arc<core::Storage>>,
+    config: core::Config,
 }
 
 impl Application for Pine {
@@ -36,35 +63,22 @@ impl Application for Pine {
     type Theme = theme::Theme;
 
     fn new(_flags: Self::Flags) -> (Self, Command<Self::Message>) {
-        match core::Storage::new_from_secret("password") {
-            Ok(storage) =>  {
-                let pine = Pine {
-                    cred_list: Vec::new(),
-                    insert_mode: InsertMode::Disabled,
-                    toasts: vec!{ Toast { message: "Add new credential".to_string(), status: Status::Info } },
-                    storage: Arc::new(storage),
-                    fault: false,
-                };
-                let fetched_fn = |res: Result<Vec<(String, Secret, String)>, StoreError>| {
-                    match res {
-                        Ok(cred_list) => Message::Storage(core::StoreMessage::Fetched(cred_list)),
-                        Err(e) => Message::Invalid(e.into()),
-                    }
-                };
-                let command = Command::perform(core::fetch(Arc::clone(&pine.storage)), fetched_fn);
-                (pine, command)
-            },
-            Err(se) => {
-                let pine = Pine {
-                    cred_list: Vec::new(),
-                    insert_mode: InsertMode::Disabled,
-                    toasts: vec!{ Toast { message: se.into(), status: Status::Danger } },
-                    storage: Arc::new(core::Storage::default()),
-                    fault: true,
-                };
-                (pine, Command::none())
-            },
-        }
+        let pine = Pine {
+            cred_list: Vec::new(),
+            insert_mode: InsertMode::Disabled,
+            lock_mode: LockMode::Locked(LockScreen::new(core::vault_exists())),
+            settings_mode: SettingsMode::Disabled,
+            toasts: Vec::new(),
+            copied: None,
+            last_activity: SystemTime::now(),
+            last_tick: SystemTime::now(),
+            search: String::new(),
+            listing: Listing::default(),
+            viewport: (0f32, 0f32),
+            storage: None,
+            config: core::Config::load(),
+        };
+        (pine, Command::none())
     }
 
     fn title(&self) -> String {
@@ -73,9 +87,29 @@ impl Application for Pine {
 
     fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
         match message {
+            Message::MasterSecretInput(secret) => if let LockMode::Locked(lock) = &mut self.lock_mode {
+                lock.secret = secret.into();
+            },
+            Message::MasterSecretConfirmInput(secret) => if let LockMode::Locked(lock) = &mut self.lock_mode {
+                if let Some(confirm) = &mut lock.confirm {
+                    *confirm = secret.into();
+                }
+            },
+            Message::Unlock => if let LockMode::Locked(lock) = &mut self.lock_mode {
+                if lock.is_valid() && !lock.unlocking {
+                    lock.unlocking = true;
+                    let unlocked_fn = |res: Result<core::Unlocked, StoreError>| {
+                        match res {
+                            Ok((storage, cred_list)) => Message::Storage(StoreMessage::Unlocked(storage, cred_list)),
+                            Err(e) => Message::Invalid(e.into()),
+                        }
+                    };
+                    return Command::perform(core::unlock(std::mem::take(&mut *lock.secret)), unlocked_fn);
+                }
+            },
             Message::InsertToggle => self.insert_mode = match self.insert_mode {
                     InsertMode::Enabled(_) => InsertMode::Disabled,
-                    InsertMode::Disabled => InsertMode::Enabled(CredInsert::default()),
+                    InsertMode::Disabled => InsertMode::Enabled(Box::default()),
             },
             Message::UsernameInput(username) => if let InsertMode::Enabled(fields) = &mut self.insert_mode {
                     fields.username = username;
@@ -83,45 +117,125 @@ impl Application for Pine {
             Message::SecretInput(secret) => if let InsertMode::Enabled(fields) = &mut self.insert_mode { fields.secret.set_secret(secret) },
             Message::Add => if let InsertMode::Enabled(fields) = &mut self.insert_mode {
                 match Cred::new(std::mem::take(&mut fields.username), std::mem::take(&mut fields.secret), std::mem::take(&mut fields.description.text())) {
-                    Ok(new_cred) => self.cred_list.push(new_cred),
+                    Ok(mut new_cred) => {
+                        new_cred.policy = fields.generator.policy();
+                        new_cred.details = fields.details.details();
+                        self.cred_list.push(new_cred);
+                    },
                     Err(_) => { eprintln!("no secret passed") },
                 };
                 fields.username = String::default();
                 fields.secret = SecretInput::default();
                 fields.description = text_editor::Content::default();
+                fields.details = DetailsForm::default();
                 self.insert_mode = InsertMode::Disabled;
+                self.refresh_list();
                 return self.update_repo(None);
             },
             Message::DescriptionInput(action) => if let InsertMode::Enabled(fields) = &mut self.insert_mode {
                 fields.description.perform(action);
             },
             Message::Cancel => self.insert_mode = InsertMode::Disabled,
-            Message::Action(i, action) => return self.update_cred(i, action),
+            Message::SearchInput(search) => {
+                self.search = search;
+                return self.reset_list();
+            },
+            Message::CycleSort => {
+                self.config.sort = cycle(&core::SortMode::ALL, self.config.sort);
+                return Command::batch([self.save_config(), self.reset_list()]);
+            },
+            Message::CycleGrouping => {
+                self.config.grouping = cycle(&core::Grouping::ALL, self.config.grouping);
+                return Command::batch([self.save_config(), self.reset_list()]);
+            },
+            Message::Scrolled(viewport) => self.viewport = (viewport.absolute_offset().y, viewport.bounds().height),
+            Message::Tick(now) => {
+                let wall = SystemTime::now();
+                let since = |time: SystemTime| wall.duration_since(time).unwrap_or_default();
+                let idle = Duration::from_secs(self.config.idle_lock_minutes.saturating_mul(60));
+                let suspended = since(self.last_tick) >= SUSPEND_GAP;
+                self.last_tick = wall;
+                if let LockMode::Unlocked = self.lock_mode {
+                    if suspended || (self.config.idle_lock_minutes > 0 && since(self.last_activity) >= idle) {
+                        return self.lock();
+                    }
+                }
+                for cred in &mut self.cred_list {
+                    cred.reveal = cred.reveal.and_then(|reveal| reveal.tick(now));
+                }
+                if let Some(copied) = &mut self.copied {
+                    if let Some(countdown) = copied.countdown {
+                        match countdown.tick(now) {
+                            Some(countdown) => copied.countdown = Some(countdown),
+                            None => return self.clear_clipboard(),
+                        }
+                        let message = copied.message();
+                        if let Some(toast) = self.toasts.iter_mut().find(|toast| toast.persistent) {
+                            toast.message = message;
+                        }
+                    }
+                }
+            },
+            Message::Focused => {
+                if let Some(id) = self.copied.as_mut().and_then(|copied| copied.then_secret.take()) {
+                    return self.update_cred(id, CredAction::YankSecret { once: false });
+                }
+                if self.copied.as_ref().is_some_and(|copied| copied.once) {
+                    return self.clear_clipboard();
+                }
+            },
+            Message::Activity => self.last_activity = SystemTime::now(),
+            Message::WindowChanged => if self.config.lock_on_minimize && matches!(self.lock_mode, LockMode::Unlocked) {
+                return window::fetch_minimized(window::Id::MAIN, Message::Minimized);
+            },
+            Message::Minimized(minimized) => if minimized == Some(true) {
+                return self.lock();
+            },
+            Message::Lock => return self.lock(),
+            Message::ClipboardChecked(unchanged) => if unchanged {
+                self.toast("Clipboard cleared", Status::Info);
+                return clipboard::write(String::new());
+            },
+            Message::FocusSearch => if let LockMode::Unlocked = self.lock_mode {
+                let id = text_input::Id::new(SEARCH_INPUT);
+                return Command::batch([text_input::focus(id.clone()), text_input::select_all(id)]);
+            },
+            Message::SettingsToggle => self.settings_mode = match self.settings_mode {
+                SettingsMode::Enabled(_) => SettingsMode::Disabled,
+                SettingsMode::Disabled => match &self.storage {
+                    Some(storage) => SettingsMode::Enabled(Box::new(SettingsPanel::new(storage.kdf(), &self.config, storage.backups()))),
+                    None => SettingsMode::Disabled,
+                },
+            },
+            Message::Settings(action) => return self.update_settings(action),
+            Message::Action(id, action) => return self.update_cred(id, action),
             Message::ToggleSecretReveal => if let InsertMode::Enabled(fields) = &mut self.insert_mode {
                 fields.reveal_secret = !fields.reveal_secret;
             },
-            Message::GenerateRandom => {}
+            Message::GenerateRandom => if let InsertMode::Enabled(fields) = &mut self.insert_mode {
+                if let Some(secret) = fields.generator.generate(fields.secret.kind()) {
+                    fields.secret.set_secret(secret);
+                }
+            },
+            Message::Generator(action) => if let InsertMode::Enabled(fields) = &mut self.insert_mode {
+                fields.generator.update(action);
+            },
+            Message::Details(action) => if let InsertMode::Enabled(fields) = &mut self.insert_mode {
+                fields.details.update(action);
+            },
             Message::SecretType(secret_type) => if let InsertMode::Enabled(fields) = &mut self.insert_mode {
                 fields.secret = match secret_type {
-                    SecretTypeMessage::Pin => SecretInput::Pin({
+                    SecretKind::Pin => SecretInput::Pin({
                         match &fields.secret {
                             SecretInput::Pin(val) if val.is_some() => Some(val.unwrap()),
-                            SecretInput::Password(val) => {
-                                match val.parse() {
-                                    Ok(i) => Some(i),
-                                    Err(_) => None,
-                                }
+                            SecretInput::Password(val) | SecretInput::Passphrase(val) => {
+                                val.parse().ok()
                             },
                             _ => None,
                         }
                     }),
-                    SecretTypeMessage::Password => SecretInput::Password({
-                        match &fields.secret {
-                            SecretInput::Password(val) => val.to_owned(),
-                            SecretInput::Pin(val) if val.is_some() => val.unwrap().to_string(),
-                            _ => String::default(),
-                        }
-                    }),
+                    SecretKind::Password => SecretInput::Password(fields.secret.get_val()),
+                    SecretKind::Passphrase => SecretInput::Passphrase(fields.secret.get_val()),
                 };
             },
             Message::CloseToast(i) => {
@@ -130,33 +244,75 @@ impl Application for Pine {
                 }
             },
             Message::Storage(store_message) => match store_message {
-                core::StoreMessage::Added => self.toast("New credential added", Status::Success),
-                core::StoreMessage::Updated => self.toast("Credential updated", Status::Success),
-                core::StoreMessage::Deleted => self.toast("Credential deleted", Status::Success),
-                core::StoreMessage::Fetched(cred_list) => {
-                    self.cred_list.extend(cred_list.into_iter().map(|cred| Cred::new_from_raw(cred.0, cred.1, cred.2)));
+                StoreMessage::Added => self.toast("New credential added", Status::Success),
+                StoreMessage::Updated => self.toast("Credential updated", Status::Success),
+                StoreMessage::Deleted => self.toast("Credential deleted", Status::Success),
+                StoreMessage::Unlocked(storage, cred_list) => {
+                    self.storage = Some(storage);
+                    self.lock_mode = LockMode::Unlocked;
+                    (self.last_activity, self.last_tick) = (SystemTime::now(), SystemTime::now());
+                    self.cred_list.extend(cred_list.into_iter().map(Cred::new_from_raw));
+                    self.refresh_list();
+                    self.toast("Vault unlocked", Status::Info);
+                },
+                // Work finished after the vault locked again must not unlock parts of it.
+                StoreMessage::Rekeyed(storage) => if let LockMode::Unlocked = self.lock_mode {
+                    self.storage = Some(storage);
+                    self.settings_mode = SettingsMode::Disabled;
+                    self.toast("Vault re-encrypted", Status::Success);
+                },
+                StoreMessage::SecretChanged(storage, deleted) => if let LockMode::Unlocked = self.lock_mode {
+                    self.storage = Some(storage);
+                    self.settings_mode = SettingsMode::Disabled;
+                    self.toast("Master password changed", Status::Success);
+                    if deleted > 0 {
+                        self.toast(&format!("{} backups of an earlier master password were deleted", deleted), Status::Info);
+                    }
                 },
-                core::StoreMessage::Invalid => self.toast("Some error occurred", Status::Danger)
+                StoreMessage::Restored(cred_list) => if let LockMode::Unlocked = self.lock_mode {
+                    self.cred_list = cred_list.into_iter().map(Cred::new_from_raw).collect();
+                    self.refresh_list();
+                    self.settings_mode = SettingsMode::Disabled;
+                    self.toast("Vault restored from backup", Status::Success);
+                },
+                StoreMessage::ConfigSaved => self.toast("Settings saved", Status::Success),
+                StoreMessage::Saved => (),
+                StoreMessage::Invalid => self.toast("Some error occurred", Status::Danger)
+            },
+            Message::Invalid(e) => {
+                if let LockMode::Locked(lock) = &mut self.lock_mode {
+                    lock.unlocking = false;
+                }
+                if let SettingsMode::Enabled(settings) = &mut self.settings_mode {
+                    settings.busy = false;
+                }
+                self.toast(e.as_str(), Status::Danger);
             },
-            Message::Invalid(e) => self.toast(e.as_str(), Status::Danger),
         };
         Command::none()
     }
 
-    fn view(&self) -> Element<Self::Message, Self::Theme> {
+    fn view(&self) -> Element<'_, Self::Message, Self::Theme> {
         let mut col = column!{ text(TITLE).style(theme::Text::Title).size(100) };
+        if let LockMode::Locked(lock) = &self.lock_mode {
+            let content = container(col.push(lock.view()).align_items(alignment::Alignment::Center).spacing(20).max_width(Pixels::from(400))).padding([0, 20, 0, 20]).center_x().width(Length::Fill);
+            return display_manager::Manager::new(content, &self.toasts, Message::CloseToast).into();
+        }
         col = match &self.insert_mode {
             InsertMode::Disabled => {
-                let button = button(button_content(Some('\u{E803}'), Some("New"), Length::Fill, None)).padding([20, 20, 20, 20]).on_press(Message::InsertToggle);
-                col.push(row!{ horizontal_space(), button, horizontal_space() })
+                let new_button = button(button_content(Some('\u{E803}'), Some("New"), Length::Fill, None)).padding([20, 20, 20, 20]).on_press(Message::InsertToggle);
+                let settings_button = button(button_content(None, Some("Settings"), Length::Fill, None)).padding([20, 20, 20, 20]).on_press(Message::SettingsToggle);
+                let lock_button = button(button_content(None, Some("Lock"), Length::Fill, None)).padding([20, 20, 20, 20]).on_press(Message::Lock);
+                col.push(row!{ horizontal_space(), new_button, settings_button, lock_button, horizontal_space() }.spacing(20))
             },
             InsertMode::Enabled(message) => {
                 let type_selector = {
                     let mut row = Row::new();
-                    for typ in [SecretTypeMessage::Password, SecretTypeMessage::Pin] {
+                    for typ in [SecretKind::Password, SecretKind::Passphrase, SecretKind::Pin] {
                         let (label, selected) = match typ {
-                            SecretTypeMessage::Password => ("Password", if let SecretInput::Password(_) = message.secret { Some(typ) } else { None }),
-                            SecretTypeMessage::Pin => ("Pin", if let SecretInput::Pin(_) = message.secret { Some(typ) } else { None }),
+                            SecretKind::Password => ("Password", if let SecretInput::Password(_) = message.secret { Some(typ) } else { None }),
+                            SecretKind::Passphrase => ("Passphrase", if let SecretInput::Passphrase(_) = message.secret { Some(typ) } else { None }),
+                            SecretKind::Pin => ("Pin", if let SecretInput::Pin(_) = message.secret { Some(typ) } else { None }),
                         };
                         row = row.push( radio(label, typ, selected, Message::SecretType) );
                     }
@@ -165,70 +321,177 @@ impl Application for Pine {
                 let cred_fields = {
                     let (secret_type, value) = match &message.secret {
                         SecretInput::Password(val) => ("password", val.to_owned()),
-                        SecretInput::Pin(val) => ("pin", val.as_ref().map(u32::to_string).unwrap_or(String::default())),
+                        SecretInput::Passphrase(val) => ("passphrase", val.to_owned()),
+                        SecretInput::Pin(val) => ("pin", val.as_ref().map(u32::to_string).unwrap_or_default().into()),
                     };
-                    let secret_row = row!{ text_input(secret_type, &value).secure(!message.reveal_secret).on_input(Message::SecretInput), button(button_content(Some(if message.reveal_secret {'\u{E801}'} else {'\u{E802}'}), None, Length::Shrink, None)).on_press(Message::ToggleSecretReveal), button(button_content(Some('\u{E800}'), None, Length::Shrink, Some(theme::Text::Black))).style(theme::Button::Distinct).on_press(Message::GenerateRandom) }.spacing(5);
-                    row!{ text_input("username", &message.username).on_input(Message::UsernameInput), secret_row }.spacing(20)
+                    let secret_row = row!{ text_input(secret_type, &value).secure(!message.reveal_secret).on_input(Message::SecretInput), button(button_content(Some(if message.reveal_secret {'\u{E801}'} else {'\u{E802}'}), None, Length::Shrink, None)).on_press(Message::ToggleSecretReveal), button(button_content(Some('\u{E800}'), None, Length::Shrink, Some(theme::Text::Black))).style(theme::Button::Distinct).on_press(Message::GenerateRandom), button(button_content(None, Some("Options"), Length::Shrink, None)).on_press(Message::Generator(GeneratorAction::Toggle)) }.spacing(5);
+                    let secret_col = Column::new().push(secret_row).push_maybe(strength_meter(&message.secret)).spacing(5);
+                    row!{ text_input("username", &message.username).on_input(Message::UsernameInput), secret_col }.spacing(20)
                 };
                 let disc = text_editor(&message.description).on_action(Message::DescriptionInput);
-                let action_buttons = row!{ button(button_content(None, Some("Cancel"), Length::Fill, None)).on_press(Message::Cancel), button(button_content(None, Some("Add"), Length::Fill, None)).on_press_maybe( message.is_not_empty().then(|| Message::Add))}.spacing(20);
-                col.push(type_selector).push(cred_fields).push(disc).push(action_buttons)
+                let action_buttons = row!{ button(button_content(None, Some("Cancel"), Length::Fill, None)).on_press(Message::Cancel), button(button_content(None, Some("Add"), Length::Fill, None)).on_press_maybe( message.is_not_empty().then_some(Message::Add))}.spacing(20);
+                col.push(type_selector).push(cred_fields).push_maybe(message.generator.view(message.secret.kind()).map(|view| view.map(Message::Generator))).push(message.details.view().map(Message::Details)).push(disc).push(action_buttons)
             }
         };
-        let list = keyed_column(self.cred_list.iter().enumerate().map(|(i, cred)| (i, cred.view().map(move |ca| Message::Action(i as i32, ca))))).spacing(20);
-        let content = container(col.push(list).align_items(alignment::Alignment::Center).spacing(20).max_width(Pixels::from(800))).padding([0, 20, 0, 20]).center_x();
+        if let (SettingsMode::Enabled(settings), Some(storage)) = (&self.settings_mode, &self.storage) {
+            col = col.push(settings.view(&self.config, storage.kdf()).map(Message::Settings));
+        }
+        let list_options = row!{
+            text_input("search (Ctrl+F)", &self.search).id(text_input::Id::new(SEARCH_INPUT)).on_input(Message::SearchInput),
+            button(button_content(None, Some(&format!("Sort: {}", self.config.sort)), Length::Shrink, None)).on_press(Message::CycleSort),
+            button(button_content(None, Some(&format!("Group: {}", self.config.grouping)), Length::Shrink, None)).on_press(Message::CycleGrouping),
+        }.spacing(5);
+        let content = container(col.push(list_options).push(self.list_view()).align_items(alignment::Alignment::Center).spacing(20).max_width(Pixels::from(800))).padding([0, 20, 20, 20]).center_x().width(Length::Fill);
+        let content = scrollable(content).id(scrollable::Id::new(CONTENT_SCROLLABLE)).on_scroll(Message::Scrolled);
         display_manager::Manager::new(content, &self.toasts, Message::CloseToast).into()
     }
 
     fn theme(&self) -> Self::Theme {
         Default::default()
     }
-}
 
-impl Pine {
-    async fn secret_reveal_timeout(sec: u64) {
-        sleep(Duration::from_secs(sec));
+    /// Listens to all key presses, so the search shortcut also works while another input has the focus,
+    /// and to input and window events that keep the vault unlocked or lock it. Minimizing has no event
+    /// of its own, so losing the focus or resizing asks the window whether it was minimized.
+    /// Ticks run for as long as the vault is unlocked, so a suspend shows as a gap between two of them.
+    fn subscription(&self) -> Subscription<Self::Message> {
+        let keys = event::listen_with(|event, _status| match event {
+            Event::Keyboard(keyboard::Event::KeyPressed { key: keyboard::Key::Character(c), modifiers, .. }) if modifiers.command() && c.as_str() == "f" => Some(Message::FocusSearch),
+            Event::Window(_, window::Event::Focused) => Some(Message::Focused),
+            Event::Window(_, window::Event::Unfocused | window::Event::Resized { .. }) => Some(Message::WindowChanged),
+            Event::Keyboard(keyboard::Event::KeyPressed { .. }) | Event::Mouse(mouse::Event::ButtonPressed(_) | mouse::Event::WheelScrolled { .. }) => Some(Message::Activity),
+            _ => None,
+        });
+        let ticks = match self.lock_mode {
+            LockMode::Unlocked => time::every(Duration::from_secs(1)).map(Message::Tick),
+            LockMode::Locked(_) => Subscription::none(),
+        };
+        Subscription::batch([keys, ticks])
     }
+}
 
-    fn update_cred(&mut self, i: i32, action: CredAction) -> Command<Message> {
-        if let Some(cred) = self.cred_list.get_mut(i as usize) {
+impl Pine {
+    /// Actions may arrive after their credential was deleted, like the `Hide` at the end of a reveal,
+    /// and are dropped then.
+    fn update_cred(&mut self, id: core::CredentialId, action: CredAction) -> Command<Message> {
+        if let Some(cred) = self.cred_list.iter_mut().find(|cred| cred.id == id) {
             match action {
                 CredAction::Reveal => {
-                    cred.hidden = false;
-                    return Command::perform(Self::secret_reveal_timeout(5), move |_| Message::Action(i, CredAction::Hide));
+                    cred.reveal = Some(Countdown::new(Duration::from_secs(self.config.reveal_seconds)));
+                    let used = core::Credential::now();
+                    cred.used = used;
+                    self.refresh_list();
+                    return self.touch_repo(id, used);
                 },
                 CredAction::Save => {
                     cred.set_creds();
+                    self.refresh_list();
                     return self.update_repo(Some(action));
                 },
                 CredAction::ToggleEdit => cred.toggle_edit(),
-                CredAction::Hide => cred.hidden = true,
-                CredAction::YankUsername => return clipboard::write(cred.username.0.to_owned()),
-                CredAction::YankSecret => return clipboard::write(cred.secret.value(false)),
+                CredAction::Hide => cred.reveal = None,
+                CredAction::YankUsername { .. } | CredAction::YankSecret { .. } | CredAction::YankUsernameThenSecret | CredAction::YankUrl(_) | CredAction::YankField(_) => {
+                    if let Some((label, copied)) = cred.copied(&action, Duration::from_secs(self.config.clipboard_seconds)) {
+                        let used = core::Credential::now();
+                        cred.used = used;
+                        self.refresh_list();
+                        let touch = self.touch_repo(id, used);
+                        return Command::batch([self.copy(&label, copied), touch]);
+                    }
+                },
                 CredAction::Delete => {
-                    self.cred_list.remove(i as usize);
+                    self.cred_list.retain(|cred| cred.id != id);
+                    self.refresh_list();
                     return self.update_repo(Some(action));
                 },
-                CredAction::DescriptionInput(_) | CredAction::SecretInput(_) | CredAction::UsernameInput(_) => cred.update(action),
+                CredAction::DescriptionInput(_) | CredAction::SecretInput(_) | CredAction::UsernameInput(_) | CredAction::GenerateRandom | CredAction::Generator(_) | CredAction::Details(_) => cred.update(action),
+            }
+        }
+        Command::none()
+    }
+
+    fn update_settings(&mut self, action: SettingsAction) -> Command<Message> {
+        if let (SettingsMode::Enabled(settings), Some(storage)) = (&mut self.settings_mode, &self.storage) {
+            match action {
+                SettingsAction::Close => self.settings_mode = SettingsMode::Disabled,
+                SettingsAction::Rekey => if let Some(kdf) = settings.kdf().filter(|_| settings.is_ready()) {
+                    settings.busy = true;
+                    let rekeyed_fn = |res: Result<Arc<core::Storage>, StoreError>| match res {
+                        Ok(storage) => Message::Storage(StoreMessage::Rekeyed(storage)),
+                        Err(e) => Message::Invalid(e.into()),
+                    };
+                    return Command::perform(core::rekey(Arc::clone(storage), std::mem::take(&mut *settings.secret), kdf), rekeyed_fn);
+                },
+                SettingsAction::ChangeSecret => if let Some(kdf) = settings.kdf().filter(|_| settings.is_ready() && settings.is_new_secret_valid()) {
+                    settings.busy = true;
+                    let changed_fn = |res: Result<(Arc<core::Storage>, usize), StoreError>| match res {
+                        Ok((storage, deleted)) => Message::Storage(StoreMessage::SecretChanged(storage, deleted)),
+                        Err(e) => Message::Invalid(e.into()),
+                    };
+                    let (secret, new_secret) = (std::mem::take(&mut *settings.secret), std::mem::take(&mut *settings.new_secret));
+                    settings.confirm_secret.zeroize();
+                    return Command::perform(core::change_secret(Arc::clone(storage), secret, new_secret, kdf), changed_fn);
+                },
+                SettingsAction::Restore(backup) => if settings.is_ready() {
+                    settings.busy = true;
+                    let restored_fn = |res: Result<Vec<core::Credential>, StoreError>| match res {
+                        Ok(cred_list) => Message::Storage(StoreMessage::Restored(cred_list)),
+                        Err(e) => Message::Invalid(e.into()),
+                    };
+                    return Command::perform(core::restore(Arc::clone(storage), std::mem::take(&mut *settings.secret), backup), restored_fn);
+                },
+                SettingsAction::ApplyConfig => if let Some(config) = settings.config(&self.config) {
+                    self.config = config;
+                    let saved_fn = |res: Result<(), StoreError>| match res {
+                        Ok(_) => Message::Storage(StoreMessage::ConfigSaved),
+                        Err(e) => Message::Invalid(e.into()),
+                    };
+                    return Command::perform(core::save_config(self.config.clone()), saved_fn);
+                },
+                _ => settings.update(action),
             }
         }
         Command::none()
     }
 
+    /// Writes a copied value to the clipboard and keeps track of it while it is due to be cleared or
+    /// followed by a secret.
+    fn copy(&mut self, label: &str, copied: Copied) -> Command<Message> {
+        let value = copied.value.to_string();
+        let tracked = copied.once || copied.countdown.is_some() || copied.then_secret.is_some();
+        self.copied = tracked.then_some(copied);
+        self.toasts.retain(|toast| !toast.persistent);
+        self.toast(&format!("{} copied", label), Status::Success);
+        if let Some(copied) = &self.copied {
+            self.toasts.push(Toast { message: copied.message(), status: Status::Info, persistent: true });
+        }
+        clipboard::write(value)
+    }
+
+    /// Empties the clipboard unless something else was copied since, which is only known once the
+    /// clipboard has been read back.
+    fn clear_clipboard(&mut self) -> Command<Message> {
+        self.toasts.retain(|toast| !toast.persistent);
+        match self.copied.take() {
+            Some(copied) => clipboard::read(move |content| Message::ClipboardChecked(content.as_deref() == Some(copied.value.as_str()))),
+            None => Command::none(),
+        }
+    }
+
     fn toast(&mut self, message: &str, status: Status) {
-        self.toasts.push(Toast { message: message.to_owned(), status });
+        self.toasts.push(Toast { message: message.to_owned(), status, persistent: false });
     }
 
     fn update_repo(&self, action: Option<CredAction>) -> Command<Message> {
         let store_result = |res: Result<(), core::StoreError>| match res {
             Ok(_) => {
                 let message = match action {
-                    None => core::StoreMessage::Added,
+                    None => StoreMessage::Added,
                     Some(action) => {
                         match action {
-                            CredAction::Delete => core::StoreMessage::Deleted,
-                            CredAction::Save => core::StoreMessage::Updated,
-                            _ => core::StoreMessage::Invalid,
+                            CredAction::Delete => StoreMessage::Deleted,
+                            CredAction::Save => StoreMessage::Updated,
+                            _ => StoreMessage::Invalid,
                         }
                     }
                 };
@@ -236,9 +499,174 @@ impl Pine {
             },
             Err(e) => Message::Invalid(e.into()),
         };
-        let creds_cloned = self.cred_list.iter().map(|cred| (cred.username.0.clone(), cred.secret.clone(), cred.description.0.clone())).collect::<Vec::<(String, Secret, String)>>();
-        Command::perform(core::save(Arc::clone(&self.storage), creds_cloned), store_result)
+        let Some(storage) = &self.storage else { return Command::none() };
+        let records = core::Records::new(self.cred_list.iter().map(Cred::credential));
+        Command::perform(core::save(Arc::clone(storage), records), store_result)
+    }
+
+    /// Records the last use of a credential next to the vault, without a toast.
+    fn touch_repo(&self, id: core::CredentialId, used: u64) -> Command<Message> {
+        let store_result = |res: Result<(), core::StoreError>| match res {
+            Ok(_) => Message::Storage(StoreMessage::Saved),
+            Err(e) => Message::Invalid(e.into()),
+        };
+        let Some(storage) = &self.storage else { return Command::none() };
+        Command::perform(core::touch(Arc::clone(storage), id, used), store_result)
+    }
+
+    /// Drops the key and every decrypted credential, clears a copied secret and returns to the master
+    /// password screen.
+    fn lock(&mut self) -> Command<Message> {
+        if let LockMode::Locked(_) = self.lock_mode {
+            return Command::none();
+        }
+        let clear = self.clear_clipboard();
+        self.cred_list = Vec::new();
+        self.storage = None;
+        self.insert_mode = InsertMode::Disabled;
+        self.settings_mode = SettingsMode::Disabled;
+        self.search.clear();
+        self.listing = Listing::default();
+        self.viewport = (0f32, 0f32);
+        self.lock_mode = LockMode::Locked(LockScreen::new(true));
+        self.toast("Vault locked", Status::Info);
+        clear
+    }
+
+    /// Filters and orders the list again for a new search or order, which starts at the top again.
+    fn reset_list(&mut self) -> Command<Message> {
+        self.refresh_list();
+        self.viewport.0 = 0f32;
+        scrollable::snap_to(scrollable::Id::new(CONTENT_SCROLLABLE), scrollable::RelativeOffset::START)
+    }
+
+    /// Credentials matching the search, ordered by relevance and then by the sort mode, in the
+    /// sections of the grouping. Sections are sorted by name, the one for credentials without tag or
+    /// folder comes last. Called whenever the credentials, the search, the order or the grouping
+    /// change, so that views only build the rows.
+    fn refresh_list(&mut self) {
+        let query = search::Query::new(&self.search);
+        let mut matches = self.cred_list.iter().enumerate().filter_map(|(index, cred)| query.matches(&cred.search_fields()).map(|m| (index, m))).collect::<Vec<_>>();
+        matches.sort_by(|(a, _), (b, _)| self.cred_list[*a].compare(&self.cred_list[*b], self.config.sort));
+        matches.sort_by_key(|(_, m)| std::cmp::Reverse(m.score));
+
+        let mut groups: Vec<(Option<&str>, Vec<usize>)> = Vec::new();
+        for (position, (index, _)) in matches.iter().enumerate() {
+            let cred = &self.cred_list[*index];
+            let names = match self.config.grouping {
+                core::Grouping::None => vec!{ None },
+                core::Grouping::Folder => vec!{ Some(cred.details.folder.as_str()).filter(|folder| !folder.is_empty()) },
+                core::Grouping::Tag if cred.details.tags.is_empty() => vec!{ None },
+                core::Grouping::Tag => cred.details.tags.iter().map(|tag| Some(tag.as_str())).collect(),
+            };
+            for name in names {
+                match groups.iter_mut().find(|(n, _)| *n == name) {
+                    Some((_, group)) => group.push(position),
+                    None => groups.push((name, vec!{ position })),
+                }
+            }
+        }
+        groups.sort_by_key(|(name, _)| (name.is_none(), name.map(str::to_lowercase)));
+        let sections = groups.into_iter().map(|(name, group)| {
+            let header = match (self.config.grouping, name) {
+                (core::Grouping::None, _) => None,
+                (core::Grouping::Tag, None) => Some("Untagged".to_string()),
+                (core::Grouping::Tag, Some(tag)) => Some(format!("#{}", tag)),
+                (core::Grouping::Folder, name) => Some(name.unwrap_or("No folder").to_string()),
+            };
+            let rows = group.into_iter().map(|position| (matches[position].0, matches[position].1.clone())).collect();
+            Section { header, rows }
+        }).collect();
+        self.listing = Listing { sections, searching: !query.is_empty() };
     }
+
+    fn save_config(&self) -> Command<Message> {
+        let saved_fn = |res: Result<(), StoreError>| match res {
+            Ok(_) => Message::Storage(StoreMessage::Saved),
+            Err(e) => Message::Invalid(e.into()),
+        };
+        Command::perform(core::save_config(self.config.clone()), saved_fn)
+    }
+
+    /// The listing as sections of rows. Only the rows near the viewport are built, the others stand
+    /// in as empty space, so large vaults stay responsive.
+    fn list_view(&self) -> Element<'_, Message, theme::Theme> {
+        let rows = self.listing.sections.iter().map(|section| section.rows.len()).sum::<usize>();
+        if rows == 0 && self.listing.searching {
+            return text("No credentials match the search").style(theme::Text::Light).into();
+        }
+        let (offset, height) = self.viewport;
+        let first = ((offset / ROW_HEIGHT) as usize).saturating_sub(LIST_MARGIN).min(rows);
+        let last = (((offset + height) / ROW_HEIGHT) as usize + LIST_MARGIN).clamp(first, rows);
+        let space = |rows: usize| (rows > 0).then(|| Space::with_height(Length::Fixed(rows as f32 * ROW_HEIGHT)));
+
+        let mut start = 0;
+        let sections = self.listing.sections.iter().filter_map(|section| {
+            let range = first.max(start)..last.min(start + section.rows.len());
+            let shown = section.rows.get(range.start.saturating_sub(start)..range.end.saturating_sub(start)).filter(|shown| !shown.is_empty());
+            start += section.rows.len();
+            let shown = shown?;
+            let header = section.header.as_ref().map(|header| text(header).style(theme::Text::Title).size(24).font(Font { weight: Weight::Bold, ..Default::default() }));
+            let list = keyed_column(shown.iter().filter_map(|(index, m)| {
+                let cred = self.cred_list.get(*index)?;
+                let id = cred.id;
+                Some((id, cred.view(Some(m)).map(move |ca| Message::Action(id, ca))))
+            })).spacing(20);
+            Some(Column::new().push_maybe(header).push(list).spacing(10).into())
+        }).collect::<Vec<Element<'_, Message, theme::Theme>>>();
+        Column::new().push_maybe(space(first)).extend(sections).push_maybe(space(rows - last)).spacing(30).align_items(Alignment::Center).into()
+    }
+}
+
+/// Credentials matching the search in the order `list_view` shows them, see `Pine::refresh_list`.
+#[derive(Default)]
+struct Listing {
+    sections: Vec<Section>,
+    searching: bool,
+}
+
+struct Section {
+    header: Option<String>,
+    /// Positions of the credentials in `Pine::cred_list`, with the highlights of their matches.
+    rows: Vec<(usize, search::Matches)>,
+}
+
+/// The value following `current` in `all`, wrapping around to the first.
+fn cycle<T: Copy + PartialEq>(all: &[T], current: T) -> T {
+    let next = all.iter().position(|value| *value == current).map_or(0, |pos| (pos + 1) % all.len());
+    all[next]
+}
+
+fn format_age(time: SystemTime) -> String {
+    let seconds = SystemTime::now().duration_since(time).unwrap_or_default().as_secs();
+    match seconds {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{} min ago", seconds / 60),
+        3600..=86399 => format!("{} h ago", seconds / 3600),
+        _ => format!("{} days ago", seconds / 86400),
+    }
+}
+
+/// Strength of a password or passphrase as a bar coloured by score, with the weaknesses found.
+/// Pins are short by nature, so they get no meter.
+fn strength_meter<'a, Message: 'a>(secret: &SecretInput) -> Option<Element<'a, Message, theme::Theme>> {
+    let (SecretInput::Password(value) | SecretInput::Passphrase(value)) = secret else { return None };
+    if value.is_empty() {
+        return None;
+    }
+    let strength = core::Strength::estimate(value);
+    let style = match strength.score {
+        0 | 1 => theme::ProgressBar::Weak,
+        2 => theme::ProgressBar::Fair,
+        _ => theme::ProgressBar::Strong,
+    };
+    let bar = progress_bar(0f32..=(core::Strength::MAX_SCORE + 1) as f32, (strength.score + 1) as f32).height(Length::Fixed(6f32)).style(style);
+    let hints = strength.hints.iter().map(ToString::to_string).collect::<Vec<String>>();
+    let label = match hints.is_empty() {
+        true => format!("≈ {:.0} bits", strength.entropy),
+        false => format!("≈ {:.0} bits, {}", strength.entropy, hints.join(", ")),
+    };
+    Some(column!(bar, text(label).style(theme::Text::Light).size(12)).spacing(4).into())
 }
 
 fn button_content<'a, Message: Clone + 'a>(codepoint: Option<char>, string: Option<&str>, width: Length, text_style: Option<theme::Text>) -> Element<'a, Message, theme::Theme> {
@@ -253,10 +681,16 @@ fn button_content<'a, Message: Clone + 'a>(codepoint: Option<char>, string: Opti
 
 #[derive(Debug)]
 struct Cred {
+    id: core::CredentialId,
     username: Username,
     secret: Secret,
     description: Description,
-    hidden: bool,
+    details: Details,
+    policy: Option<core::GeneratorPolicy>,
+    created: u64,
+    modified: u64,
+    used: u64,
+    reveal: Option<Countdown>,
     edit_mode: Option<CredEdit>,
 }
 
@@ -265,46 +699,123 @@ struct NoSecret;
 impl Cred {
     fn new(username: String, secret: SecretInput, description: String) -> Result<Self, NoSecret> {
         let secret = match secret {
-            SecretInput::Password(pass) => Secret::Password(pass),
-            SecretInput::Pin(pin) if pin.is_some() => Secret::Pin(pin.unwrap().to_string()),
+            SecretInput::Password(mut pass) => Secret(core::Secret::Password(std::mem::take(&mut *pass))),
+            SecretInput::Passphrase(mut pass) => Secret(core::Secret::Passphrase(std::mem::take(&mut *pass))),
+            SecretInput::Pin(pin) if pin.is_some() => Secret(core::Secret::Pin(pin.unwrap().to_string())),
             _ => return Err(NoSecret),
         };
         Ok(Self {
+            id: core::CredentialId::random(),
             username: Username::new(username),
             secret,
             description: Description::new(description),
-            hidden: true,
+            details: Details::default(),
+            policy: None,
+            created: core::Credential::now(),
+            modified: core::Credential::now(),
+            used: 0,
+            reveal: None,
             edit_mode: None,
         })
     }
 
-    fn new_from_raw(username: String, secret: Secret, description: String) -> Self {
+    fn new_from_raw(cred: core::Credential) -> Self {
         Self {
-            username: Username::new(username),
-            secret,
-            description: Description::new(description),
-            hidden: true,
+            id: cred.id,
+            username: Username::new(cred.username),
+            secret: Secret(cred.secret),
+            description: Description::new(cred.description),
+            details: Details { title: cred.title, folder: cred.folder, urls: cred.urls, tags: cred.tags, fields: cred.fields },
+            policy: cred.policy,
+            created: cred.created,
+            modified: cred.modified,
+            used: cred.used,
+            reveal: None,
             edit_mode: None,
         }
     }
     
-    fn view(&self) -> Element<CredAction, theme::Theme> {
+    fn credential(&self) -> core::Credential {
+        core::Credential {
+            id: self.id,
+            title: self.details.title.clone(),
+            username: self.username.0.clone(),
+            secret: self.secret.0.clone(),
+            description: self.description.0.clone(),
+            folder: self.details.folder.clone(),
+            urls: self.details.urls.clone(),
+            tags: self.details.tags.clone(),
+            fields: self.details.fields.clone(),
+            policy: self.policy.clone(),
+            created: self.created,
+            modified: self.modified,
+            used: self.used,
+        }
+    }
+
+    fn compare(&self, other: &Self, sort: core::SortMode) -> Ordering {
+        let name = |cred: &Self| match cred.details.title.is_empty() {
+            true => cred.username.0.to_lowercase(),
+            false => cred.details.title.to_lowercase(),
+        };
+        match sort {
+            core::SortMode::Alphabetical => name(self).cmp(&name(other)),
+            core::SortMode::RecentlyUsed => other.used.cmp(&self.used),
+            core::SortMode::RecentlyModified => other.modified.cmp(&self.modified),
+            core::SortMode::Created => self.created.cmp(&other.created),
+        }
+    }
+
+    /// Fields searched by the filter bar, in the order `view` expects their highlights.
+    fn search_fields(&self) -> [String; 5] {
+        [self.details.title.clone(), self.username.0.clone(), self.secret.kind().to_string(), self.description.0.clone(), self.details.tags.join(" ")]
+    }
+
+    fn view<'a>(&'a self, matches: Option<&search::Matches>) -> Element<'a, CredAction, theme::Theme> {
+        let highlights = |i: usize| matches.and_then(|m| m.positions.get(i)).map(Vec::as_slice).unwrap_or_default();
         let details_col = {
-            let cred_row = row!(self.username.view(self.edit_mode.as_ref().map(|em| em.username.as_ref())), self.secret.view(self.hidden, self.edit_mode.as_ref().map(|em| &em.secret))).spacing(5);
-            column!( cred_row, self.description.view(self.edit_mode.as_ref().map(|em| &em.description)) ).spacing(5).width(Length::Fill)
+            let cred_row = row!(self.username.view(self.edit_mode.as_ref().map(|em| em.username.as_ref()), highlights(1)), self.secret.view(self.reveal.map(|reveal| reveal.seconds_left), self.edit_mode.as_ref().map(|em| &em.secret), highlights(2))).spacing(5);
+            let generator = self.edit_mode.as_ref().and_then(|em| em.generator.view(em.secret.kind())).map(|view| view.map(CredAction::Generator));
+            let (header, details) = match &self.edit_mode {
+                Some(em) => (None, Some(em.details.view().map(CredAction::Details))),
+                None => (self.details.header(highlights(0), highlights(4)), self.details.view(self.reveal.is_none(), core::Credential::now())),
+            };
+            Column::new().push_maybe(header).push(cred_row).push_maybe(generator).push_maybe(details).push(self.description.view(self.edit_mode.as_ref().map(|em| &em.description), highlights(3))).spacing(5).width(Length::Fill)
         };
         let action_col = {
             let button_from_icon = |cp: char, a: Option<CredAction>| button(button_content(Some(cp), None, Length::Fixed(20f32), None)).on_press_maybe(a);
-            let save = self.edit_mode.as_ref().map(|ce| button_from_icon('\u{E808}', ce.is_not_empty().then(|| CredAction::Save)));
+            let save = self.edit_mode.as_ref().map(|ce| button_from_icon('\u{E808}', ce.is_not_empty().then_some(CredAction::Save)));
             Column::new().push_maybe(save).push(button_from_icon(if self.edit_mode.is_some() { '\u{E807}'} else { '\u{E804}' }, Some(CredAction::ToggleEdit))).push(button_from_icon('\u{E805}', Some(CredAction::Delete))).spacing(4)
         };
         container(row!( details_col, action_col ).spacing(4).padding(8).height(Length::Shrink)).style(theme::Container::Cred).into()
     }
 
+    /// What a copy action puts on the clipboard, with the name of the copied field for the toast.
+    /// Secrets, hidden fields and TOTP codes are cleared again after `clear_after`, `None` when there
+    /// is nothing to copy, like a field with an invalid TOTP key.
+    fn copied(&self, action: &CredAction, clear_after: Duration) -> Option<(String, Copied)> {
+        let countdown = Some(Countdown::new(clear_after));
+        match *action {
+            CredAction::YankUsername { once } => Some(("Username".to_string(), Copied::new(self.username.0.to_owned(), None, once))),
+            CredAction::YankSecret { once } => Some((self.secret.kind().to_string(), Copied::new(self.secret.0.value().to_owned(), countdown, once))),
+            CredAction::YankUsernameThenSecret => Some(("Username".to_string(), Copied { then_secret: Some(self.id), ..Copied::new(self.username.0.to_owned(), None, false) })),
+            CredAction::YankUrl(i) => self.details.urls.get(i).map(|url| ("URL".to_string(), Copied::new(url.to_owned(), None, false))),
+            CredAction::YankField(i) => self.details.fields.get(i).and_then(|field| {
+                let label = if field.name.is_empty() { "Field" } else { &field.name };
+                match field.kind {
+                    core::FieldKind::Totp => core::Totp::parse(&field.value).and_then(|totp| totp.code(core::Credential::now())).map(|code| (label.to_owned(), Copied::new(code, countdown, false))),
+                    core::FieldKind::Hidden => Some((label.to_owned(), Copied::new(field.value.to_owned(), countdown, false))),
+                    _ => Some((label.to_owned(), Copied::new(field.value.to_owned(), None, false))),
+                }
+            }),
+            _ => None,
+        }
+    }
+
     fn toggle_edit(&mut self) {
         self.edit_mode = match self.edit_mode {
             Some(_) => None,
-            None => Some(CredEdit::new_from(&self.username.0, &self.secret.value(false), self.secret.kind(), &self.description.0)),
+            None => Some(CredEdit::new_from(&self.username.0, self.secret.0.value(), self.secret.kind(), &self.description.0, &self.details, self.policy.as_ref())),
         };
     }
 
@@ -314,6 +825,11 @@ impl Cred {
                 CredAction::UsernameInput(username) => ce.username = username,
                 CredAction::SecretInput(secret) => ce.secret.set_secret(secret),
                 CredAction::DescriptionInput(description) => ce.description.perform(description),
+                CredAction::GenerateRandom => if let Some(secret) = ce.generator.generate(ce.secret.kind()) {
+                    ce.secret.set_secret(secret);
+                },
+                CredAction::Generator(action) => ce.generator.update(action),
+                CredAction::Details(action) => ce.details.update(action),
                 _ => unreachable!(),
             }
         }
@@ -324,46 +840,612 @@ impl Cred {
             self.username.update(std::mem::take(&mut new_values.username));
             self.secret.update(std::mem::take(&mut new_values.secret));
             self.description.update(std::mem::take(&mut new_values.description));
+            self.details = new_values.details.details();
+            self.modified = core::Credential::now();
+            self.policy = new_values.generator.policy();
             self.toggle_edit();
         }
     }
 }
 
+/// A deadline, like the end of a reveal, with the whole seconds left until then as of the last tick.
+#[derive(Clone, Copy, Debug)]
+struct Countdown {
+    until: Instant,
+    seconds_left: u64,
+}
+
+impl Countdown {
+    /// A deadline too far away to represent has passed right away, so nothing stays revealed or
+    /// copied for longer than asked.
+    fn new(duration: Duration) -> Self {
+        let now = Instant::now();
+        Self { until: now.checked_add(duration).unwrap_or(now), seconds_left: duration.as_secs() }
+    }
+
+    /// `None` once the deadline has passed.
+    fn tick(self, now: Instant) -> Option<Self> {
+        let left = self.until.checked_duration_since(now).filter(|left| !left.is_zero())?;
+        Some(Self { seconds_left: left.as_secs_f64().ceil() as u64, ..self })
+    }
+}
+
+/// A ring emptying clockwise over the period of a one-time password, `left` being the share of the
+/// period remaining.
+struct Ring {
+    left: f32,
+}
+
+impl<Message> canvas::Program<Message, theme::Theme> for Ring {
+    type State = ();
+
+    fn draw(&self, _state: &(), renderer: &Renderer, theme: &theme::Theme, bounds: Rectangle, _cursor: mouse::Cursor) -> Vec<canvas::Geometry> {
+        use iced::widget::text::StyleSheet;
+        let color = |style| theme.appearance(style).color.unwrap_or_default();
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        let radius = frame.width().min(frame.height()) / 2.0 - 2.0;
+        frame.stroke(&canvas::Path::circle(frame.center(), radius), canvas::Stroke::default().with_color(color(theme::Text::Gray)).with_width(1.0));
+        let start = -std::f32::consts::FRAC_PI_2;
+        let arc = canvas::path::Arc { center: frame.center(), radius, start_angle: Radians(start), end_angle: Radians(start + self.left * std::f32::consts::TAU) };
+        frame.stroke(&canvas::Path::new(|p| p.arc(arc)), canvas::Stroke::default().with_color(color(theme::Text::Title)).with_width(3.0));
+        vec!{ frame.into_geometry() }
+    }
+}
+
+/// A value pine put on the clipboard, cleared again when its countdown ends. Pastes into other
+/// applications cannot be observed, so a value copied once is also cleared as soon as pine is focused
+/// again, which is where the user returns to after pasting it. A username copied with `then_secret`
+/// is replaced by the secret of that credential at the same point, for login pages asking for one
+/// after the other.
+struct Copied {
+    value: Zeroizing<String>,
+    countdown: Option<Countdown>,
+    once: bool,
+    then_secret: Option<core::CredentialId>,
+}
+
+impl Copied {
+    fn new(value: String, countdown: Option<Countdown>, once: bool) -> Self {
+        Self { value: value.into(), countdown, once, then_secret: None }
+    }
+
+    fn message(&self) -> String {
+        if self.then_secret.is_some() {
+            return format!("Secret follows on return to {}", TITLE);
+        }
+        match (self.countdown, self.once) {
+            (Some(countdown), false) => format!("Clipboard clears in {}s", countdown.seconds_left),
+            (Some(countdown), true) => format!("Clipboard clears on return to {} or in {}s", TITLE, countdown.seconds_left),
+            (None, _) => format!("Clipboard clears on return to {}", TITLE),
+        }
+    }
+}
+
 enum InsertMode {
-    Enabled(CredInsert),
+    Enabled(Box<CredInsert>),
+    Disabled,
+}
+
+enum LockMode {
+    Locked(LockScreen),
+    Unlocked,
+}
+
+#[derive(Default)]
+struct LockScreen {
+    secret: Zeroizing<String>,
+    confirm: Option<Zeroizing<String>>,
+    unlocking: bool,
+}
+
+impl LockScreen {
+    fn new(vault_exists: bool) -> Self {
+        Self {
+            confirm: (!vault_exists).then(Zeroizing::default),
+            ..Default::default()
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        !self.secret.is_empty() && self.confirm.as_ref().is_none_or(|confirm| *confirm == self.secret)
+    }
+
+    fn view(&self) -> Element<'_, Message, theme::Theme> {
+        let secret = text_input("master password", &self.secret).secure(true).on_input(Message::MasterSecretInput).on_submit(Message::Unlock);
+        let confirm = self.confirm.as_ref().map(|confirm| text_input("confirm master password", confirm).secure(true).on_input(Message::MasterSecretConfirmInput).on_submit(Message::Unlock));
+        let label = match (&self.confirm, self.unlocking) {
+            (_, true) => "Unlocking…",
+            (Some(_), false) => "Create vault",
+            (None, false) => "Unlock",
+        };
+        let unlock_button = button(button_content(None, Some(label), Length::Fill, None)).on_press_maybe((self.is_valid() && !self.unlocking).then_some(Message::Unlock));
+        Column::new().push(secret).push_maybe(confirm).push(unlock_button).spacing(20).into()
+    }
+}
+
+enum SettingsMode {
+    Enabled(Box<SettingsPanel>),
     Disabled,
 }
 
+#[derive(Default)]
+struct SettingsPanel {
+    kdf_memory: String,
+    kdf_iterations: String,
+    kdf_parallelism: String,
+    secret: Zeroizing<String>,
+    new_secret: Zeroizing<String>,
+    confirm_secret: Zeroizing<String>,
+    backups: String,
+    reveal_seconds: String,
+    clipboard_seconds: String,
+    idle_lock_minutes: String,
+    lock_on_minimize: bool,
+    backup_list: Vec<core::Backup>,
+    busy: bool,
+}
+
+impl SettingsPanel {
+    fn new(kdf: core::Kdf, config: &core::Config, backup_list: Vec<core::Backup>) -> Self {
+        let kdf = if let core::Kdf::Argon2id { .. } = kdf { kdf } else { core::Kdf::default() };
+        let core::Kdf::Argon2id { memory_kib, iterations, parallelism } = kdf else { return Self::default() };
+        Self {
+            kdf_memory: (memory_kib / 1024).to_string(),
+            kdf_iterations: iterations.to_string(),
+            kdf_parallelism: parallelism.to_string(),
+            backups: config.backups.to_string(),
+            reveal_seconds: config.reveal_seconds.to_string(),
+            clipboard_seconds: config.clipboard_seconds.to_string(),
+            idle_lock_minutes: config.idle_lock_minutes.to_string(),
+            lock_on_minimize: config.lock_on_minimize,
+            backup_list,
+            ..Default::default()
+        }
+    }
+
+    /// Re-keying, changing the master password and restoring all need the current master password
+    /// and must not overlap.
+    fn is_ready(&self) -> bool {
+        !self.secret.is_empty() && !self.busy
+    }
+
+    fn is_new_secret_valid(&self) -> bool {
+        !self.new_secret.is_empty() && self.new_secret == self.confirm_secret
+    }
+
+    /// The parameters of the panel, if they are all numbers within the bounds of `core::Kdf`.
+    fn kdf(&self) -> Option<core::Kdf> {
+        let memory_mib = self.kdf_memory.parse::<u32>().ok()?;
+        Some(core::Kdf::Argon2id {
+            memory_kib: memory_mib.checked_mul(1024)?,
+            iterations: self.kdf_iterations.parse().ok()?,
+            parallelism: self.kdf_parallelism.parse().ok()?,
+        }).filter(core::Kdf::is_valid)
+    }
+
+    /// `current` with the preferences of the panel, if they are all valid.
+    fn config(&self, current: &core::Config) -> Option<core::Config> {
+        Some(core::Config {
+            backups: self.backups.parse().ok()?,
+            reveal_seconds: self.reveal_seconds.parse().ok().filter(|seconds| core::Config::REVEAL_SECONDS.contains(seconds))?,
+            clipboard_seconds: self.clipboard_seconds.parse().ok().filter(|seconds| core::Config::CLIPBOARD_SECONDS.contains(seconds))?,
+            idle_lock_minutes: self.idle_lock_minutes.parse().ok()?,
+            lock_on_minimize: self.lock_on_minimize,
+            ..current.clone()
+        })
+    }
+
+    fn update(&mut self, action: SettingsAction) {
+        match action {
+            SettingsAction::KdfMemoryInput(value) => self.kdf_memory = value,
+            SettingsAction::KdfIterationsInput(value) => self.kdf_iterations = value,
+            SettingsAction::KdfParallelismInput(value) => self.kdf_parallelism = value,
+            SettingsAction::SecretInput(secret) => self.secret = secret.into(),
+            SettingsAction::NewSecretInput(secret) => self.new_secret = secret.into(),
+            SettingsAction::ConfirmSecretInput(secret) => self.confirm_secret = secret.into(),
+            SettingsAction::BackupsInput(value) => self.backups = value,
+            SettingsAction::RevealSecondsInput(value) => self.reveal_seconds = value,
+            SettingsAction::ClipboardSecondsInput(value) => self.clipboard_seconds = value,
+            SettingsAction::IdleLockMinutesInput(value) => self.idle_lock_minutes = value,
+            SettingsAction::LockOnMinimizeToggle(lock) => self.lock_on_minimize = lock,
+            SettingsAction::Rekey | SettingsAction::ChangeSecret | SettingsAction::Restore(_) | SettingsAction::ApplyConfig | SettingsAction::Close => unreachable!(),
+        }
+    }
+
+    fn view(&self, config: &core::Config, current_kdf: core::Kdf) -> Element<'_, SettingsAction, theme::Theme> {
+        let labelled = |label: &'static str, input: Element<'static, SettingsAction, theme::Theme>| -> Element<'static, SettingsAction, theme::Theme> {
+            column!{ text(label).style(theme::Text::Light), input }.spacing(5).width(Length::Fill).into()
+        };
+        let current = text(format!("Key derivation: {}", current_kdf)).style(theme::Text::Light);
+        let upgrade_hint = (current_kdf != core::Kdf::default()).then(|| text("Re-key the vault to upgrade to Argon2id").style(theme::Text::Title));
+        let kdf_row = row!{
+            labelled("Memory (1 to 2048 MiB)", text_input("memory", &self.kdf_memory).on_input(SettingsAction::KdfMemoryInput).into()),
+            labelled("Iterations (1 to 64)", text_input("iterations", &self.kdf_iterations).on_input(SettingsAction::KdfIterationsInput).into()),
+            labelled("Lanes (1 to 16)", text_input("lanes", &self.kdf_parallelism).on_input(SettingsAction::KdfParallelismInput).into()),
+        }.spacing(20);
+        let rekey = (self.is_ready() && self.kdf().is_some()).then_some(SettingsAction::Rekey);
+        let rekey_button = button(button_content(None, Some("Re-key vault"), Length::Fill, None)).on_press_maybe(rekey);
+        let config_row = row!{
+            labelled("Backups to keep", text_input("backups", &self.backups).on_input(SettingsAction::BackupsInput).on_submit(SettingsAction::ApplyConfig).into()),
+            labelled("Reveal secrets for (1 to 3600 seconds)", text_input("seconds", &self.reveal_seconds).on_input(SettingsAction::RevealSecondsInput).on_submit(SettingsAction::ApplyConfig).into()),
+            labelled("Clear clipboard after (1 to 3600 seconds)", text_input("seconds", &self.clipboard_seconds).on_input(SettingsAction::ClipboardSecondsInput).on_submit(SettingsAction::ApplyConfig).into()),
+        }.spacing(20).align_items(Alignment::End);
+        let lock_row = row!{
+            labelled("Lock when idle for (minutes, 0 for never)", text_input("minutes", &self.idle_lock_minutes).on_input(SettingsAction::IdleLockMinutesInput).on_submit(SettingsAction::ApplyConfig).into()),
+            checkbox("Lock when minimized", self.lock_on_minimize).on_toggle(SettingsAction::LockOnMinimizeToggle).width(Length::Fill),
+            button(button_content(None, Some("Apply"), Length::Fill, None)).on_press_maybe(self.config(config).is_some().then_some(SettingsAction::ApplyConfig)),
+        }.spacing(20).align_items(Alignment::End);
+        let backup_list = self.backup_list.iter().map(|backup| {
+            let label = text(format!("Backup {}, saved {}", backup.index, format_age(backup.modified))).style(theme::Text::Light).width(Length::Fill);
+            let restore_button = button(button_content(None, Some("Restore"), Length::Shrink, None)).on_press_maybe(self.is_ready().then_some(SettingsAction::Restore(backup.index)));
+            row!{ label, restore_button }.spacing(20).align_items(Alignment::Center).into()
+        });
+        let secret = text_input("current master password, required for the actions below", &self.secret).secure(true).on_input(SettingsAction::SecretInput);
+        let change_secret = (self.is_ready() && self.is_new_secret_valid() && self.kdf().is_some()).then_some(SettingsAction::ChangeSecret);
+        let new_secret_row = row!{
+            text_input("new master password", &self.new_secret).secure(true).on_input(SettingsAction::NewSecretInput),
+            text_input("confirm new master password", &self.confirm_secret).secure(true).on_input(SettingsAction::ConfirmSecretInput).on_submit(SettingsAction::ChangeSecret),
+        }.spacing(20);
+        let change_secret_button = button(button_content(None, Some("Change master password"), Length::Fill, None)).on_press_maybe(change_secret);
+        let close_button = button(button_content(None, Some("Close"), Length::Fill, None)).on_press(SettingsAction::Close);
+        let content = Column::new().push(secret).push(new_secret_row).push(change_secret_button).push(current).push_maybe(upgrade_hint).push(kdf_row).push(rekey_button).push(config_row).push(lock_row).extend(backup_list).push(close_button).spacing(10);
+        container(content).padding(8).style(theme::Container::Cred).into()
+    }
+}
+
+/// Options of the password generator in the insert and edit forms. The policy is only remembered
+/// with the credential once a password has been generated with it.
+#[derive(Debug)]
+struct GeneratorPanel {
+    policy: core::GeneratorPolicy,
+    length: String,
+    words: String,
+    visible: bool,
+    used: bool,
+}
+
+impl Default for GeneratorPanel {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl GeneratorPanel {
+    fn new(policy: Option<&core::GeneratorPolicy>) -> Self {
+        let used = policy.is_some();
+        let policy = policy.cloned().unwrap_or_default();
+        Self {
+            length: policy.length.to_string(),
+            words: policy.words.to_string(),
+            policy,
+            visible: false,
+            used,
+        }
+    }
+
+    fn generate(&mut self, kind: SecretKind) -> Option<String> {
+        let secret = core::Secret::generate(kind, &self.policy)?;
+        self.used |= kind != SecretKind::Pin;
+        Some(secret.value().to_owned())
+    }
+
+    fn policy(&self) -> Option<core::GeneratorPolicy> {
+        self.used.then(|| self.policy.clone())
+    }
+
+    fn update(&mut self, action: GeneratorAction) {
+        match action {
+            GeneratorAction::Toggle => self.visible = !self.visible,
+            GeneratorAction::LengthInput(value) => if value.is_empty() || value.parse::<usize>().is_ok() {
+                self.policy.length = value.parse().unwrap_or_default();
+                self.length = value;
+            },
+            GeneratorAction::Uppercase(checked) => self.policy.uppercase = checked,
+            GeneratorAction::Lowercase(checked) => self.policy.lowercase = checked,
+            GeneratorAction::Digits(checked) => self.policy.digits = checked,
+            GeneratorAction::Symbols(checked) => self.policy.symbols = checked,
+            GeneratorAction::ExcludeAmbiguous(checked) => self.policy.exclude_ambiguous = checked,
+            GeneratorAction::RequireEach(checked) => self.policy.require_each = checked,
+            GeneratorAction::SymbolSetInput(symbols) => self.policy.symbol_set = symbols,
+            GeneratorAction::WordsInput(value) => if value.is_empty() || value.parse::<usize>().is_ok() {
+                self.policy.words = value.parse().unwrap_or_default();
+                self.words = value;
+            },
+            GeneratorAction::SeparatorInput(separator) => self.policy.separator = separator,
+            GeneratorAction::Capitalize(checked) => self.policy.capitalize = checked,
+            GeneratorAction::AppendDigit(checked) => self.policy.append_digit = checked,
+        }
+    }
+
+    /// Only the options that apply to `kind` are shown, pins are always six digits.
+    fn view(&self, kind: SecretKind) -> Option<Element<'_, GeneratorAction, theme::Theme>> {
+        if !self.visible {
+            return None;
+        }
+        let option = |label: &str, checked: bool, action: fn(bool) -> GeneratorAction| checkbox(label, checked).on_toggle(action);
+        fn labelled<'a>(label: &str, input: Element<'a, GeneratorAction, theme::Theme>) -> Row<'a, GeneratorAction, theme::Theme> {
+            row!{ text(label).style(theme::Text::Light), input }.spacing(10).align_items(Alignment::Center)
+        }
+        let options: Element<GeneratorAction, theme::Theme> = match kind {
+            SecretKind::Password => {
+                let length = labelled("Length", text_input("length", &self.length).on_input(GeneratorAction::LengthInput).width(Length::Fixed(60f32)).into());
+                let classes = row!{
+                    length,
+                    option("A-Z", self.policy.uppercase, GeneratorAction::Uppercase),
+                    option("a-z", self.policy.lowercase, GeneratorAction::Lowercase),
+                    option("0-9", self.policy.digits, GeneratorAction::Digits),
+                    option("Symbols", self.policy.symbols, GeneratorAction::Symbols),
+                }.spacing(20).align_items(Alignment::Center);
+                let rules = row!{
+                    option("Exclude ambiguous characters", self.policy.exclude_ambiguous, GeneratorAction::ExcludeAmbiguous),
+                    option("At least one of each", self.policy.require_each, GeneratorAction::RequireEach),
+                }.spacing(20);
+                let symbol_set = self.policy.symbols.then(|| text_input("symbols", &self.policy.symbol_set).on_input(GeneratorAction::SymbolSetInput));
+                Column::new().push(classes).push(rules).push_maybe(symbol_set).spacing(10).into()
+            },
+            SecretKind::Passphrase => row!{
+                labelled("Words", text_input("words", &self.words).on_input(GeneratorAction::WordsInput).width(Length::Fixed(60f32)).into()),
+                labelled("Separator", text_input("none", &self.policy.separator).on_input(GeneratorAction::SeparatorInput).width(Length::Fixed(60f32)).into()),
+                option("Capitalize", self.policy.capitalize, GeneratorAction::Capitalize),
+                option("Append a digit", self.policy.append_digit, GeneratorAction::AppendDigit),
+            }.spacing(20).align_items(Alignment::Center).into(),
+            SecretKind::Pin => Column::new().into(),
+        };
+        let strength = match self.policy.is_valid(kind) {
+            true => text(format!("≈ {:.0} bits of entropy", self.policy.entropy(kind))).style(theme::Text::Light),
+            false => text("No secret satisfies these options").style(theme::Text::Danger),
+        };
+        Some(Column::new().push(options).push(strength).spacing(10).into())
+    }
+}
+
+/// Title, urls, tags and custom fields of a credential.
+#[derive(Clone, Debug, Default)]
+struct Details {
+    title: String,
+    folder: String,
+    urls: Vec<String>,
+    tags: Vec<String>,
+    fields: Vec<core::CustomField>,
+}
+
+impl Details {
+    /// `tag_highlights` are positions in the tags joined by spaces, as searched by the filter bar.
+    fn header(&self, title_highlights: &[usize], tag_highlights: &[usize]) -> Option<Element<'_, CredAction, theme::Theme>> {
+        if self.title.is_empty() && self.folder.is_empty() && self.tags.is_empty() {
+            return None;
+        }
+        let title = search::highlighted(&self.title, title_highlights, |t| t.style(theme::Text::Title).size(20).font(Font { weight: Weight::Bold, ..Default::default() }));
+        let folder = (!self.folder.is_empty()).then(|| text(format!("in {}", self.folder)).style(theme::Text::Gray));
+        let mut offset = 0;
+        let tags = self.tags.iter().map(|tag| {
+            let positions = tag_highlights.iter().filter(|p| (offset..offset + tag.chars().count()).contains(p)).map(|p| p - offset + 1).collect::<Vec<usize>>();
+            offset += tag.chars().count() + 1;
+            search::highlighted(&format!("#{}", tag), &positions, |t| t.style(theme::Text::Gray))
+        });
+        Some(Row::new().push(title).push_maybe(folder).extend(tags).spacing(10).align_items(Alignment::Center).into())
+    }
+
+    /// Values of hidden fields are masked like the secret while `hidden` is set. TOTP fields show the
+    /// code valid at `now` instead of their key, with a ring counting down to the next one.
+    fn view(&self, hidden: bool, now: u64) -> Option<Element<'_, CredAction, theme::Theme>> {
+        if self.urls.is_empty() && self.fields.is_empty() {
+            return None;
+        }
+        let entry = |name: String, value: Element<'static, CredAction, theme::Theme>, copy: Option<CredAction>| -> Element<'_, CredAction, theme::Theme> {
+            let name = text(name).style(theme::Text::Title).font(Font { weight: Weight::Bold, ..Default::default() });
+            let copy_button = button(button_content(Some('\u{E806}'), None, Length::Shrink, Some(theme::Text::Gray))).style(theme::Button::Cred).on_press_maybe(copy);
+            row!(name, value, copy_button).spacing(8).align_items(Alignment::Center).into()
+        };
+        let urls = self.urls.iter().enumerate().map(|(i, url)| entry("URL:".to_string(), text(url).style(theme::Text::Light).into(), Some(CredAction::YankUrl(i))));
+        let fields = self.fields.iter().enumerate().map(|(i, field)| {
+            let value: Element<_, _> = match field.kind {
+                core::FieldKind::Totp => match core::Totp::parse(&field.value).and_then(|totp| Some((totp.code(now)?, totp))) {
+                    Some((code, totp)) => {
+                        let ring = canvas(Ring { left: totp.seconds_left(now) as f32 / totp.period as f32 }).width(16).height(16);
+                        row!(text(code).style(theme::Text::Light).font(Font::MONOSPACE), ring).spacing(8).align_items(Alignment::Center).into()
+                    },
+                    None => return entry(format!("{}:", field.name), text("Invalid TOTP key").style(theme::Text::Danger).into(), None),
+                },
+                core::FieldKind::Hidden if hidden => text(format!("{:•^1$}", String::default(), field.value.chars().count())).style(theme::Text::Light).into(),
+                _ => text(&field.value).style(theme::Text::Light).into(),
+            };
+            entry(format!("{}:", field.name), value, Some(CredAction::YankField(i)))
+        });
+        Some(Column::new().extend(urls).extend(fields).spacing(5).into())
+    }
+}
+
+/// Inputs for the details of a credential in the insert and edit forms. Urls are separated by
+/// whitespace and tags by commas.
+#[derive(Debug, Default)]
+struct DetailsForm {
+    title: String,
+    folder: String,
+    urls: String,
+    tags: String,
+    fields: Vec<core::CustomField>,
+}
+
+impl DetailsForm {
+    fn new(details: &Details) -> Self {
+        Self {
+            title: details.title.to_owned(),
+            folder: details.folder.to_owned(),
+            urls: details.urls.join(" "),
+            tags: details.tags.join(", "),
+            fields: details.fields.clone(),
+        }
+    }
+
+    fn details(&self) -> Details {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in self.tags.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+            if !tags.iter().any(|t| t == tag) {
+                tags.push(tag.to_owned());
+            }
+        }
+        Details {
+            title: self.title.trim().to_owned(),
+            folder: self.folder.trim().to_owned(),
+            urls: self.urls.split_whitespace().map(str::to_owned).collect(),
+            tags,
+            fields: self.fields.iter().filter(|field| !field.name.trim().is_empty() || !field.value.is_empty()).cloned().collect(),
+        }
+    }
+
+    fn update(&mut self, action: DetailsAction) {
+        match action {
+            DetailsAction::TitleInput(title) => self.title = title,
+            DetailsAction::FolderInput(folder) => self.folder = folder,
+            DetailsAction::UrlsInput(urls) => self.urls = urls,
+            DetailsAction::TagsInput(tags) => self.tags = tags,
+            DetailsAction::AddField => self.fields.push(core::CustomField::default()),
+            DetailsAction::RemoveField(i) => if i < self.fields.len() {
+                self.fields.remove(i);
+            },
+            DetailsAction::FieldNameInput(i, name) => if let Some(field) = self.fields.get_mut(i) {
+                field.name = name;
+            },
+            DetailsAction::FieldKind(i) => if let Some(field) = self.fields.get_mut(i) {
+                field.kind = cycle(&core::FieldKind::ALL, field.kind);
+            },
+            DetailsAction::FieldValueInput(i, value) => if let Some(field) = self.fields.get_mut(i) {
+                field.value = value;
+            },
+        }
+    }
+
+    fn view(&self) -> Element<'_, DetailsAction, theme::Theme> {
+        let title_row = row!{
+            text_input("title", &self.title).on_input(DetailsAction::TitleInput),
+            text_input("folder", &self.folder).on_input(DetailsAction::FolderInput),
+            text_input("tags, separated by commas", &self.tags).on_input(DetailsAction::TagsInput),
+        }.spacing(5);
+        let urls = text_input("urls, separated by spaces", &self.urls).on_input(DetailsAction::UrlsInput);
+        let fields = self.fields.iter().enumerate().map(|(i, field)| {
+            let placeholder = match field.kind {
+                core::FieldKind::Text | core::FieldKind::Hidden => "value",
+                core::FieldKind::Url => "https://",
+                core::FieldKind::Email => "name@example.com",
+                core::FieldKind::Totp => "otpauth://totp/… or base32 key",
+            };
+            let secure = matches!(field.kind, core::FieldKind::Hidden | core::FieldKind::Totp);
+            row!{
+                text_input("name", &field.name).on_input(move |name| DetailsAction::FieldNameInput(i, name)).width(Length::Fixed(150f32)),
+                button(button_content(None, Some(&field.kind.to_string()), Length::Fixed(60f32), None)).on_press(DetailsAction::FieldKind(i)),
+                text_input(placeholder, &field.value).secure(secure).on_input(move |value| DetailsAction::FieldValueInput(i, value)),
+                button(button_content(Some('\u{E805}'), None, Length::Shrink, None)).on_press(DetailsAction::RemoveField(i)),
+            }.spacing(5).align_items(Alignment::Center).into()
+        });
+        let add_field = button(button_content(Some('\u{E803}'), Some("Field"), Length::Shrink, None)).on_press(DetailsAction::AddField);
+        Column::new().push(title_row).push(urls).extend(fields).push(add_field).spacing(5).into()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum DetailsAction {
+    TitleInput(String),
+    FolderInput(String),
+    UrlsInput(String),
+    TagsInput(String),
+    AddField,
+    RemoveField(usize),
+    FieldNameInput(usize, String),
+    FieldKind(usize),
+    FieldValueInput(usize, String),
+}
+
+#[derive(Clone, Debug)]
+pub enum GeneratorAction {
+    Toggle,
+    LengthInput(String),
+    Uppercase(bool),
+    Lowercase(bool),
+    Digits(bool),
+    Symbols(bool),
+    ExcludeAmbiguous(bool),
+    RequireEach(bool),
+    SymbolSetInput(String),
+    WordsInput(String),
+    SeparatorInput(String),
+    Capitalize(bool),
+    AppendDigit(bool),
+}
+
+#[derive(Clone, Debug)]
+pub enum SettingsAction {
+    KdfMemoryInput(String),
+    KdfIterationsInput(String),
+    KdfParallelismInput(String),
+    SecretInput(String),
+    NewSecretInput(String),
+    ConfirmSecretInput(String),
+    BackupsInput(String),
+    RevealSecondsInput(String),
+    ClipboardSecondsInput(String),
+    IdleLockMinutesInput(String),
+    LockOnMinimizeToggle(bool),
+    Rekey,
+    ChangeSecret,
+    Restore(usize),
+    ApplyConfig,
+    Close,
+}
+
 #[derive(Clone, Debug)]
 pub enum Message {
+    MasterSecretInput(String),
+    MasterSecretConfirmInput(String),
+    Unlock,
     InsertToggle,
-    Action(i32, CredAction),
+    Action(core::CredentialId, CredAction),
     UsernameInput(String),
     SecretInput(String),
     DescriptionInput(text_editor::Action),
     Add,
     Cancel,
+    SearchInput(String),
+    FocusSearch,
+    CycleSort,
+    CycleGrouping,
+    Scrolled(scrollable::Viewport),
+    Tick(Instant),
+    Focused,
+    Activity,
+    /// The window lost the focus or was resized, so it may have been minimized.
+    WindowChanged,
+    /// Whether the window is minimized, `None` where the platform cannot tell, like on Wayland.
+    Minimized(Option<bool>),
+    Lock,
+    /// Whether the clipboard still held the copied value when it was due to be cleared.
+    ClipboardChecked(bool),
     ToggleSecretReveal,
     GenerateRandom,
-    SecretType(SecretTypeMessage),
+    Generator(GeneratorAction),
+    Details(DetailsAction),
+    SecretType(SecretKind),
+    SettingsToggle,
+    Settings(SettingsAction),
     CloseToast(usize),
-    Storage(core::StoreMessage),
+    Storage(StoreMessage),
     Invalid(String),
 }
 
-#[derive(Clone, Debug, Copy, Eq, PartialEq)]
-pub enum SecretTypeMessage {
-    Password,
-    Pin,
-}
-
-impl fmt::Display for SecretTypeMessage {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::Password => "Password",
-            Self::Pin => "Pin",
-        }.fmt(f)
-    }
+#[derive(Clone, Debug)]
+pub enum StoreMessage {
+    Unlocked(Arc<core::Storage>, Vec<core::Credential>),
+    Rekeyed(Arc<core::Storage>),
+    /// With the number of backups that could not be moved to the new master password.
+    SecretChanged(Arc<core::Storage>, usize),
+    Restored(Vec<core::Credential>),
+    ConfigSaved,
+    /// Saved in the background, without notifying the user.
+    Saved,
+    Added,
+    Deleted,
+    Updated,
+    Invalid,
 }
 
 #[derive(Clone, Debug)]
@@ -376,8 +1458,15 @@ pub enum CredAction {
     DescriptionInput(text_editor::Action),
     Reveal,
     Hide,
-    YankUsername,
-    YankSecret,
+    YankUsername { once: bool },
+    YankSecret { once: bool },
+    /// Copies the username, and the secret once pine is focused again.
+    YankUsernameThenSecret,
+    YankUrl(usize),
+    YankField(usize),
+    GenerateRandom,
+    Generator(GeneratorAction),
+    Details(DetailsAction),
 }
 
 #[derive(Debug, Default)]
@@ -385,6 +1474,8 @@ struct CredEdit {
     username: String,
     secret: SecretInput,
     description: text_editor::Content,
+    details: DetailsForm,
+    generator: GeneratorPanel,
 }
 
 impl CredEdit {
@@ -392,11 +1483,13 @@ impl CredEdit {
         !self.username.is_empty() && !self.secret.is_empty()
     }
 
-    fn new_from(username: &str, secret: &str, kind: SecretTypeMessage, description: &str) -> Self {
+    fn new_from(username: &str, secret: &str, kind: SecretKind, description: &str, details: &Details, policy: Option<&core::GeneratorPolicy>) -> Self {
         Self {
             username: username.to_owned(),
             secret: SecretInput::new_from(secret, kind),
             description: text_editor::Content::with_text(description),
+            details: DetailsForm::new(details),
+            generator: GeneratorPanel::new(policy),
         }
     }
 }
@@ -407,6 +1500,8 @@ struct CredInsert {
     secret: SecretInput,
     reveal_secret: bool,
     description: text_editor::Content,
+    details: DetailsForm,
+    generator: GeneratorPanel,
 }
 
 impl CredInsert {
@@ -415,29 +1510,40 @@ impl CredInsert {
     }
 }
 
+/// Typed secrets are wiped whenever they are replaced, by the next key press or by the credential
+/// they were saved to.
 #[derive(Clone, Debug)]
 enum SecretInput {
-    Password(String),
+    Password(Zeroizing<String>),
+    Passphrase(Zeroizing<String>),
     Pin(Option<u32>),
 }
 
 impl Default for SecretInput {
     fn default() -> Self {
-        Self::Password(String::default())
+        Self::Password(Zeroizing::default())
     }
 }
 
 impl SecretInput {
     fn is_empty(&self) -> bool {
         match self {
-            Self::Password(value) => value.is_empty(),
+            Self::Password(value) | Self::Passphrase(value) => value.is_empty(),
             Self::Pin(value) => value.is_none(),
         }
     }
 
+    fn kind(&self) -> SecretKind {
+        match self {
+            Self::Password(_) => SecretKind::Password,
+            Self::Passphrase(_) => SecretKind::Passphrase,
+            Self::Pin(_) => SecretKind::Pin,
+        }
+    }
+
     fn set_secret(&mut self, secret: String) {
         match self {
-            Self::Password(pass) => *pass = secret,
+            Self::Password(pass) | Self::Passphrase(pass) => *pass = secret.into(),
             Self::Pin(pass) => *pass = match secret.is_empty() {
                 true => None,
                 false => match secret.parse() {
@@ -448,17 +1554,18 @@ impl SecretInput {
         }
     }
 
-    fn new_from(val: &str, kind: SecretTypeMessage) -> Self {
+    fn new_from(val: &str, kind: SecretKind) -> Self {
         match kind {
-            SecretTypeMessage::Password => Self::Password(val.to_owned()),
-            SecretTypeMessage::Pin => Self::Pin(val.parse().ok()),
+            SecretKind::Password => Self::Password(val.to_owned().into()),
+            SecretKind::Passphrase => Self::Passphrase(val.to_owned().into()),
+            SecretKind::Pin => Self::Pin(val.parse().ok()),
         }
     }
 
-    fn get_val(&self) -> String {
+    fn get_val(&self) -> Zeroizing<String> {
         match self {
-            Self::Password(val) => val.to_owned(),
-            Self::Pin(val) => val.map(|v| v.to_string()).unwrap_or(String::default()),
+            Self::Password(val) | Self::Passphrase(val) => val.clone(),
+            Self::Pin(val) => val.map(|v| v.to_string()).unwrap_or_default().into(),
         }
     }
 }
@@ -471,14 +1578,16 @@ impl Username {
         Self(username)
     }
 
-    fn view(&self, edit_mode: Option<&str>) -> Container<CredAction, theme::Theme> {
+    fn view(&self, edit_mode: Option<&str>, highlights: &[usize]) -> Container<'_, CredAction, theme::Theme> {
         let content: Element<CredAction, theme::Theme> = match edit_mode {
             Some(input) => text_input("username", input).on_input(CredAction::UsernameInput).into(),
             None => {
                 let title = text("Username:").style(theme::Text::Title).font(Font { weight: Weight::Bold, ..Default::default() });
-                let text = text(&self.0).style(theme::Text::Light);
-                let copy_button = button(button_content(Some('\u{E806}'), None, Length::Shrink, Some(theme::Text::Gray))).style(theme::Button::Cred).on_press(CredAction::YankUsername);
-                row!(title, text, copy_button).spacing(8).align_items(Alignment::Center).into()
+                let copy_once_button = button(text("once").style(theme::Text::Gray)).style(theme::Button::Cred).on_press(CredAction::YankUsername { once: true });
+                let sequence_button = button(text("then secret").style(theme::Text::Gray)).style(theme::Button::Cred).on_press(CredAction::YankUsernameThenSecret);
+                let text = search::highlighted(&self.0, highlights, |t| t.style(theme::Text::Light));
+                let copy_button = button(button_content(Some('\u{E806}'), None, Length::Shrink, Some(theme::Text::Gray))).style(theme::Button::Cred).on_press(CredAction::YankUsername { once: false });
+                row!(title, text, copy_button, copy_once_button, sequence_button).spacing(8).align_items(Alignment::Center).into()
             },
         };
         container(content).center_x().width(Length::Fill)
@@ -490,76 +1599,45 @@ impl Username {
 }
 
 #[derive(Clone, Debug)]
-pub enum Secret {
-    Password(String),
-    Pin(String),
-}
-
-impl fmt::Display for Secret {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let (kind, secret) = match self {
-            Self::Password(sec) => ("password", sec.as_str()),
-            Self::Pin(sec) => ("pin", sec.as_str()),
-        };
-        write!(f, "{}:{}", kind, secret)
-    }
-}
-
-impl From<String> for Secret {
-    fn from(value: String) -> Self {
-        Self::from(value.as_str())
-    }
-}
-
-impl From<&str> for Secret {
-    fn from(value: &str) -> Self {
-        let (kind, secret) = value.split_once(':').unwrap();
-        match kind {
-            "password" => Self::Password(secret.to_owned()),
-            "pin" => Self::Pin(secret.to_owned()),
-            _ => unreachable!(),
-        }
-    }
-}
+struct Secret(core::Secret);
 
 impl Secret {
-    fn value(&self, hidden: bool) -> String {
-        let secret = match self {
-            Self::Password(value) => value,
-            Self::Pin(value) => value,
-        };
+    /// Borrows the secret when it is shown, so that views leave no copies of it behind.
+    fn value(&self, hidden: bool) -> Cow<'_, str> {
+        let secret = self.0.value();
         match hidden {
-            true => format!("{:•^1$}", String::default(), secret.len()),
-            false => secret.to_owned(),
+            true => Cow::Owned(format!("{:•^1$}", String::default(), secret.len())),
+            false => Cow::Borrowed(secret),
         }
     }
 
-    fn kind(&self) -> SecretTypeMessage {
-        match self {
-            Self::Password(_) => SecretTypeMessage::Password,
-            Self::Pin(_) => SecretTypeMessage::Pin,
-        }
+    fn kind(&self) -> SecretKind {
+        self.0.kind()
     }
 
-    fn view(&self, hidden: bool, edit_mode: Option<&SecretInput>) -> Container<CredAction, theme::Theme> {
+    /// `seconds_left` counts down while the secret is revealed, and is `None` while it is hidden.
+    fn view(&self, seconds_left: Option<u64>, edit_mode: Option<&SecretInput>, highlights: &[usize]) -> Container<'_, CredAction, theme::Theme> {
         let content: Element<CredAction, theme::Theme> = match edit_mode {
             Some(val) => {
                 let kind = match val {
                     SecretInput::Password(_) => "password",
+                    SecretInput::Passphrase(_) => "passphrase",
                     SecretInput::Pin(_) => "pin",
                 };
-                text_input(kind, &val.get_val()).on_input(CredAction::SecretInput).into()
+                let generate_button = button(button_content(Some('\u{E800}'), None, Length::Shrink, Some(theme::Text::Gray))).style(theme::Button::Cred).on_press(CredAction::GenerateRandom);
+                let options_button = button(button_content(None, Some("Options"), Length::Shrink, Some(theme::Text::Gray))).style(theme::Button::Cred).on_press(CredAction::Generator(GeneratorAction::Toggle));
+                let input_row = row!(text_input(kind, &val.get_val()).on_input(CredAction::SecretInput), generate_button, options_button).spacing(5).align_items(Alignment::Center);
+                Column::new().push(input_row).push_maybe(strength_meter(val)).spacing(5).into()
             },
             None => {
-                let kind = match self {
-                    Self::Password(_) => "Password",
-                    Self::Pin(_) => "Pin",
-                };
-                let title = text( kind ).style(theme::Text::Title).font(Font { weight: Weight::Bold, ..Default::default() });
-                let text = text(self.value(hidden)).style(theme::Text::Light);
+                let hidden = seconds_left.is_none();
+                let title = search::highlighted(&self.kind().to_string(), highlights, |t| t.style(theme::Text::Title).font(Font { weight: Weight::Bold, ..Default::default() }));
+                let countdown = seconds_left.map(|seconds| text(format!("{}s", seconds)).style(theme::Text::Gray));
+                let copy_once_button = button(text("once").style(theme::Text::Gray)).style(theme::Button::Cred).on_press(CredAction::YankSecret { once: true });
+                let text = Text::new(self.value(hidden)).style(theme::Text::Light);
                 let view_button = button(button_content(Some(if hidden {'\u{E802}'} else {'\u{E801}'}), None, Length::Shrink, Some(theme::Text::Gray))).style(theme::Button::Cred).on_press(if hidden { CredAction::Reveal } else { CredAction::Hide });
-                let copy_button = button(button_content(Some('\u{E806}'), None, Length::Shrink, Some(theme::Text::Gray))).style(theme::Button::Cred).on_press(CredAction::YankSecret);
-                row!(title, text, view_button, copy_button).spacing(8).align_items(Alignment::Center).into()
+                let copy_button = button(button_content(Some('\u{E806}'), None, Length::Shrink, Some(theme::Text::Gray))).style(theme::Button::Cred).on_press(CredAction::YankSecret { once: false });
+                Row::new().push(title).push(text).push_maybe(countdown).push(view_button).push(copy_button).push(copy_once_button).spacing(8).align_items(Alignment::Center).into()
             },
         };
 
@@ -567,15 +1645,21 @@ impl Secret {
     }
 
     fn update(&mut self, input: SecretInput) {
-        match self {
-            Self::Password(pass) => *pass = match input {
-                SecretInput::Password(val) => val,
-                SecretInput::Pin(_) => String::default(),
+        match &mut self.0 {
+            core::Secret::Password(pass) | core::Secret::Passphrase(pass) => {
+                pass.zeroize();
+                *pass = match input {
+                    SecretInput::Password(mut val) | SecretInput::Passphrase(mut val) => std::mem::take(&mut *val),
+                    SecretInput::Pin(_) => String::default(),
+                };
+            },
+            core::Secret::Pin(pin) => {
+                pin.zeroize();
+                *pin = match input {
+                    SecretInput::Pin(val) if val.is_some() => val.unwrap().to_string(),
+                    _ => String::default(),
+                };
             },
-            Self::Pin(pin) => *pin = match input {
-                SecretInput::Pin(val) if val.is_some() => val.unwrap().to_string(),
-                _ => String::default(),
-            }
         }
     }
 }
@@ -588,10 +1672,10 @@ impl Description {
         Self(description.trim().to_owned())
     }
 
-    fn view<'a>(&'a self, edit_mode: Option<&'a text_editor::Content>) -> Element<CredAction, theme::Theme>  {
+    fn view<'a>(&'a self, edit_mode: Option<&'a text_editor::Content>, highlights: &[usize]) -> Element<'a, CredAction, theme::Theme>  {
         match edit_mode {
-            Some(description) => text_editor(description).on_action(CredAction::DescriptionInput).height(Length::Fill).into(),
-            None => text(&self.0).style(theme::Text::Light).into(),
+            Some(description) => text_editor(description).on_action(CredAction::DescriptionInput).into(),
+            None => search::highlighted(&self.0, highlights, |t| t.style(theme::Text::Light)),
         }
     }
 
@@ -603,6 +1687,8 @@ impl Description {
 pub struct Toast {
     message: String,
     status: Status,
+    /// Stays up until it is closed or removed instead of closing by itself.
+    persistent: bool,
 }
 
 enum Status {
@@ -621,6 +1707,120 @@ impl std::fmt::Display for Status {
     }
 }
 
+mod search {
+    use iced::{widget::{text, Column, Row, Text}, Element};
+    use crate::theme;
+
+    /// Whitespace separated terms, each of which has to match one of the searched fields. Terms match
+    /// case insensitively, either as a substring or fuzzily as a subsequence of the field.
+    pub struct Query {
+        terms: Vec<Vec<char>>,
+    }
+
+    /// Positions of the matched characters in every searched field, and a score that ranks better matches first.
+    #[derive(Clone, Debug, Default, PartialEq)]
+    pub struct Matches {
+        pub score: u32,
+        pub positions: Vec<Vec<usize>>,
+    }
+
+    impl Query {
+        pub fn new(query: &str) -> Self {
+            Self { terms: query.split_whitespace().map(|term| term.chars().map(fold).collect()).collect() }
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.terms.is_empty()
+        }
+
+        /// Every field matches an empty query.
+        pub fn matches(&self, fields: &[String]) -> Option<Matches> {
+            let fields = fields.iter().map(|field| field.chars().map(fold).collect()).collect::<Vec<Vec<char>>>();
+            let mut matches = Matches { score: 0, positions: vec!{ Vec::new(); fields.len() } };
+            for term in &self.terms {
+                let (i, score, positions) = fields.iter().enumerate().filter_map(|(i, field)| find(term, field).map(|(score, positions)| (i, score, positions))).max_by_key(|(_, score, _)| *score)?;
+                matches.score += score;
+                matches.positions[i].extend(positions);
+            }
+            for positions in &mut matches.positions {
+                positions.sort_unstable();
+                positions.dedup();
+            }
+            Some(matches)
+        }
+    }
+
+    fn fold(c: char) -> char {
+        c.to_lowercase().next().unwrap_or(c)
+    }
+
+    /// Substrings score above 200 and more if they start a word. Fuzzy matches take the shortest window
+    /// ending at the first complete subsequence and score up to 100, less the more it is spread out.
+    fn find(term: &[char], field: &[char]) -> Option<(u32, Vec<usize>)> {
+        if term.is_empty() || term.len() > field.len() {
+            return None;
+        }
+        if let Some(start) = field.windows(term.len()).position(|window| window == term) {
+            let word_start = start == 0 || !field[start - 1].is_alphanumeric();
+            return Some((200 + if word_start { 100 } else { 0 }, (start..start + term.len()).collect()));
+        }
+        let mut chars = term.iter().peekable();
+        let end = field.iter().position(|c| {
+            chars.next_if_eq(&c);
+            chars.peek().is_none()
+        })?;
+        let mut positions = Vec::with_capacity(term.len());
+        let mut chars = term.iter().rev().peekable();
+        for (i, c) in field[..=end].iter().enumerate().rev() {
+            if chars.next_if_eq(&c).is_some() {
+                positions.push(i);
+            }
+        }
+        positions.reverse();
+        let span = end + 1 - positions[0];
+        Some(((100 * term.len() / span) as u32, positions))
+    }
+
+    /// `value` formatted by `format`, with the characters at `positions` highlighted in one row of text
+    /// segments per line.
+    pub fn highlighted<'a, Message: 'a>(value: &str, positions: &[usize], format: impl Fn(Text<'a, theme::Theme>) -> Text<'a, theme::Theme>) -> Element<'a, Message, theme::Theme> {
+        if positions.is_empty() {
+            return format(text(value)).into();
+        }
+        let mut offset = 0;
+        let lines = value.split('\n').map(|line| {
+            let mut segments: Vec<(bool, String)> = Vec::new();
+            for (i, c) in line.chars().enumerate() {
+                let highlight = positions.binary_search(&(offset + i)).is_ok();
+                match segments.last_mut() {
+                    Some((h, segment)) if *h == highlight => segment.push(c),
+                    _ => segments.push((highlight, c.to_string())),
+                }
+            }
+            offset += line.chars().count() + 1;
+            let segments = segments.into_iter().map(|(highlight, segment)| match highlight {
+                true => format(text(segment)).style(theme::Text::Highlight).into(),
+                false => format(text(segment)).into(),
+            });
+            Row::with_children(segments).into()
+        });
+        Column::with_children(lines).into()
+    }
+
+    #[test]
+    fn fuzzy_search() {
+        let fields = ["Mail".to_string(), "alice@example.com".to_string()];
+        let matches = Query::new("MAIL").matches(&fields).expect("no match");
+        assert_eq!(matches.positions, vec!{ vec!{0, 1, 2, 3}, Vec::new() });
+        let matches = Query::new("ace examp").matches(&fields).expect("no match");
+        assert_eq!(matches.positions, vec!{ Vec::new(), vec!{0, 3, 4, 6, 7, 8, 9, 10} });
+        assert!(matches.score < Query::new("alice examp").matches(&fields).expect("no match").score);
+        assert_eq!(find(&['a', 'b'], &['a', 'x', 'a', 'y', 'b']), Some((66, vec!{2, 4})));
+        assert!(Query::new("mail bob").matches(&fields).is_none());
+        assert_eq!(Query::new(" ").matches(&fields), Some(Matches { score: 0, positions: vec!{ Vec::new(); 2 } }));
+    }
+}
+
 mod display_manager {
     use std::time::{Duration, Instant};
     use iced::{advanced::{graphics::core::event, layout, overlay, widget::{self, Tree}, Layout, Shell, Widget}, widget::{button, container, row, text}, window, Alignment, Element, Event, Length, Point, Renderer, Size};
@@ -631,11 +1831,14 @@ mod display_manager {
     pub struct Manager<'a> {
         content: Element<'a, Message, Theme>,
         toasts: Vec<Element<'a, Message, Theme, Renderer>>,
+        /// Indices of the toasts that close by themselves, in the order of the instants kept in the tree state.
+        timed: Vec<usize>,
         on_close: Box<dyn Fn(usize) -> Message + 'a>,
     }
 
     impl<'a> Manager<'a> {
         pub fn new(content: impl Into<Element<'a, Message, Theme>>, toasts: &'a [Toast], on_close: impl Fn(usize) -> Message + 'a) -> Self {
+            let timed = toasts.iter().enumerate().filter(|(_, toast)| !toast.persistent).map(|(index, _)| index).collect();
             let toasts = toasts.iter().enumerate().map(|(index, toast)| {
                 let (text_style, container_style) = match toast.status {
                     Status::Info => (Text::Black, Container::InfoToast),
@@ -648,6 +1851,7 @@ mod display_manager {
             Self {
                 content: content.into(),
                 toasts,
+                timed,
                 on_close: Box::new(on_close),
             }
         }
@@ -683,9 +1887,9 @@ mod display_manager {
             let instants = tree.state.downcast_mut::<Vec<Option<Instant>>>();
             instants.retain(Option::is_some);
 
-            match (instants.len(), self.toasts.len()) {
+            match (instants.len(), self.timed.len()) {
                 (old, new) if old > new => instants.truncate(new),
-                (old, new) if old < new => instants.extend(std::iter::repeat(Some(Instant::now())).take(new - old)),
+                (old, new) if old < new => instants.extend(std::iter::repeat_n(Some(Instant::now()), new - old)),
                 _ => {},
             }
             tree.diff_children(&std::iter::once(&self.content).chain(self.toasts.iter()).collect::<Vec<&Element<'a, Message, Theme, Renderer>>>())
@@ -714,6 +1918,7 @@ mod display_manager {
                     toasts: &mut self.toasts,
                     state: toast_state,
                     instants,
+                    timed: &self.timed,
                     on_close: &self.on_close,
                 }))
             });
@@ -732,6 +1937,7 @@ mod display_manager {
         toasts: &'b mut [Element<'a, Message, Theme>],
         state: &'b mut [Tree],
         instants: &'b mut [Option<Instant>],
+        timed: &'b [usize],
         on_close: &'b dyn Fn(usize) -> Message,
     }
 
@@ -744,7 +1950,7 @@ mod display_manager {
         fn on_event(&mut self, event: iced::Event, layout: Layout<'_>, cursor: iced::advanced::mouse::Cursor, renderer: &Renderer, clipboard: &mut dyn iced::advanced::Clipboard, shell: &mut iced::advanced::Shell<'_, Message>) -> iced::advanced::graphics::core::event::Status {
             if let Event::Window(_, window::Event::RedrawRequested(now)) = event {
                 let mut next_redraw = None;
-                self.instants.iter_mut().enumerate().for_each(|(index, maybe_instant)| {
+                self.instants.iter_mut().zip(self.timed).for_each(|(maybe_instant, &index)| {
                     if let Some(instant) = maybe_instant.as_mut() {
                         let remaining = TOAST_DURATION.saturating_sub(instant.elapsed());
                         if let Duration::ZERO = remaining {
@@ -764,12 +1970,14 @@ mod display_manager {
             }
 
             let viewport = layout.bounds();
-            self.toasts.iter_mut().zip(self.state.iter_mut()).zip(layout.children()).zip(self.instants.iter_mut()).map(|(((child, state), layout), instant)| {
+            self.toasts.iter_mut().enumerate().zip(self.state.iter_mut()).zip(layout.children()).map(|(((index, child), state), layout)| {
                 let mut local_messages = Vec::new();
                 let mut local_shell = Shell::new(&mut local_messages);
                 let status = child.as_widget_mut().on_event(state, event.clone(), layout, cursor, renderer, clipboard, &mut local_shell, &viewport);
                 if !local_shell.is_empty() {
-                    instant.take();
+                    if let Some(instant) = self.timed.iter().position(|&timed| timed == index).and_then(|position| self.instants.get_mut(position)) {
+                        instant.take();
+                    }
                 }
                 shell.merge(local_shell, std::convert::identity);
                 status
@@ -803,7 +2011,7 @@ mod display_manager {
 }
 
 mod theme {
-    use iced::{application, border::Radius, color, widget::{button, container, radio, text, text_editor, text_input}, Background, Border, Color};
+    use iced::{application, border::Radius, color, widget::{button, checkbox, container, progress_bar, radio, scrollable, text, text_editor, text_input}, Background, Border, Color};
 
     #[derive(Default)]
     pub struct Theme;
@@ -870,6 +2078,8 @@ mod theme {
         Black,
         Gray,
         Light,
+        Danger,
+        Highlight,
     }
 
     impl text::StyleSheet for Theme {
@@ -883,6 +2093,8 @@ mod theme {
                     Text::Black => Color::BLACK,
                     Text::Gray => Self::GRAY,
                     Text::Light => Self::BEIGE,
+                    Text::Danger => Self::RED,
+                    Text::Highlight => Self::SELECTION,
                 }),
             }
         }
@@ -995,6 +2207,80 @@ mod theme {
         }
     }
 
+    #[derive(Default)]
+    pub enum ProgressBar {
+        Weak,
+        #[default]
+        Fair,
+        Strong,
+    }
+
+    impl progress_bar::StyleSheet for Theme {
+        type Style = ProgressBar;
+
+        fn appearance(&self, style: &Self::Style) -> progress_bar::Appearance {
+            progress_bar::Appearance {
+                background: Background::Color(Self::GRAY),
+                bar: Background::Color(match style {
+                    ProgressBar::Weak => Self::RED,
+                    ProgressBar::Fair => Self::SECONDARY,
+                    ProgressBar::Strong => Self::GREEN,
+                }),
+                border_radius: Radius::from(3),
+            }
+        }
+    }
+
+    impl scrollable::StyleSheet for Theme {
+        type Style = ();
+
+        fn active(&self, _style: &Self::Style) -> scrollable::Appearance {
+            scrollable::Appearance {
+                container: container::Appearance::default(),
+                scrollbar: scrollable::Scrollbar {
+                    background: None,
+                    border: Border::default(),
+                    scroller: scrollable::Scroller {
+                        color: Self::TERTIARY,
+                        border: Border {
+                            radius: Radius::from(5),
+                            ..Default::default()
+                        },
+                    },
+                },
+                gap: None,
+            }
+        }
+
+        fn hovered(&self, style: &Self::Style, is_mouse_over_scrollbar: bool) -> scrollable::Appearance {
+            let mut appearance = self.active(style);
+            if is_mouse_over_scrollbar {
+                appearance.scrollbar.scroller.color = Self::SECONDARY;
+            }
+            appearance
+        }
+    }
+
+    impl checkbox::StyleSheet for Theme {
+        type Style = ();
+
+        fn active(&self, _style: &Self::Style, _is_checked: bool) -> checkbox::Appearance {
+            checkbox::Appearance {
+                background: Background::Color(Self::SECONDARY),
+                icon_color: Self::PRIMARY,
+                border: Border {
+                    radius: Radius::from(3),
+                    ..Default::default()
+                },
+                text_color: Some(Self::SECONDARY),
+            }
+        }
+
+        fn hovered(&self, style: &Self::Style, is_checked: bool) -> checkbox::Appearance {
+            self.active(style, is_checked)
+        }
+    }
+
     impl radio::StyleSheet for Theme {
         type Style = ();
         
@@ -1015,344 +2301,304 @@ mod theme {
     }
 }
 
-mod core {
-    use std::{fs, io::{self, Write}, path, sync::Arc, time};
-    use aes::{cipher::{generic_array::GenericArray, BlockDecrypt, BlockEncrypt, KeyInit}, Aes128};
-    use rand::{Rng, RngCore};
-    use crate::{Secret, TITLE};
-
-    #[derive(Debug)]
-    pub struct Storage {
-        cipher: Aes128,
-        directory: path::PathBuf,
-        file_name: String,
+/// Headless access to the vault for terminals and scripts, used whenever `pine` is started with arguments.
+mod cli {
+    use std::{io::{self, BufRead, IsTerminal}, sync::Arc, time::Duration};
+    use iced::futures::executor::block_on;
+    use zeroize::Zeroizing;
+    use crate::{core, SecretKind, TITLE};
+
+    const USAGE: &str = "\
+usage: pine [--password-fd FD] <command>
+
+commands:
+    list                                        list titles and usernames without their secrets
+    get <n>                                     print the secret of credential n
+    add <username> [--pin | --passphrase] [--description D]
+                                                add a credential, reading its secret from stdin or a prompt
+    edit <n> [--username U] [--description D] [--pin | --password | --passphrase] [--secret]
+                                                change credential n, --secret reads a new secret
+    rm <n>                                      remove credential n
+    copy <n> [username]                         copy the secret or username of credential n to the clipboard
+    help                                        show this message
+
+The master password is prompted for on the terminal, or read from the first line of FD.
+Without arguments pine starts the graphical interface.";
+
+    #[derive(Debug, PartialEq)]
+    enum Command {
+        List,
+        Get(usize),
+        Add { username: String, kind: SecretKind, description: String },
+        Edit { index: usize, username: Option<String>, kind: Option<SecretKind>, secret: bool, description: Option<String> },
+        Remove(usize),
+        Copy { index: usize, username: bool },
+        Help,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Invocation {
+        command: Command,
+        secret_fd: Option<i32>,
+    }
+
+    /// Runs the command given by `args` and returns the process exit code.
+    pub fn run(args: Vec<String>) -> i32 {
+        let invocation = match parse(args) {
+            Ok(invocation) => invocation,
+            Err(e) => {
+                eprintln!("{}: {}\n\n{}", TITLE, e, USAGE);
+                return 2;
+            },
+        };
+        match execute(invocation) {
+            Ok(_) => 0,
+            Err(e) => {
+                eprintln!("{}: {}", TITLE, e);
+                1
+            },
+        }
     }
 
-    impl Default for Storage {
-        fn default() -> Self {
-            Self {
-                cipher: Aes128::new(&GenericArray::from([0u8; 16])),
-                directory: path::PathBuf::default(),
-                file_name: String::default(),
+    fn parse(args: Vec<String>) -> Result<Invocation, String> {
+        let mut secret_fd = None;
+        let mut rest = Vec::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--password-fd" => secret_fd = Some(option_value(&mut args, &arg)?.parse().map_err(|_| format!("{} expects a file descriptor", arg))?),
+                _ => rest.push(arg),
             }
         }
-    }
 
-    impl Storage {
-        pub fn new_from_secret(secret: &str) -> Result<Self, StoreError> {
-            const N: u32 = 100_000;
-            let directory = {
-                let home_dir = home::home_dir().unwrap_or_default();
-                if cfg!(target_os = "windows") {
-                    home_dir.join("AppData").join(TITLE.to_lowercase())
-                }
-                else if cfg!(target_os = "macos") {
-                    home_dir.join("Library").join("Application Support").join(TITLE.to_lowercase())
-                }
-                else {
-                    home_dir.join(".config").join(TITLE.to_lowercase())
-                }
-            };
-            let salt = Self::read_salt(directory.clone())?;
-            let key = pbkdf2::pbkdf2_hmac_array::<sha2::Sha256, 16>(secret.as_bytes(), salt.as_slice(), N);
-            let cipher = Aes128::new(&GenericArray::from(key));
-            let file_name = "localstorage.aes".to_string();
-            Ok(Self {
-                cipher,
-                file_name,
-                directory,
-            })
-        }
-
-        fn read_salt(directory: path::PathBuf) -> Result<Vec<u8>, StoreError> {
-            const SALT_EXTENTION: &str = "salt";
-            let mut rng = rand::thread_rng();
-
-            let file_name= {
-                let length = rng.gen_range(10 - SALT_EXTENTION.len()..=30 - SALT_EXTENTION.len());
-                let random = (0..length).map(|_| {
-                    let choice = rng.gen_range(0u8..3);
-                    match choice {
-                        0 => rng.gen_range('a'..='z'),
-                        1 => rng.gen_range('A'..='Z'),
-                        2 => rng.gen_range('0'..='9'),
-                        _ => unreachable!(),
-                    }
-                }).collect::<String>();
-                format!("{}{}", random, SALT_EXTENTION)
-            };
-
-            let create_new_salt_file = |c: Option<Vec<u8>>| {
-                match c {
-                    Some(content) =>  {
-                        fs::write(directory.join(file_name), content.as_slice()).map_err(StoreError::IO)?;
-                        Ok(content)
-                    },
-                    None => {
-                        let salt_len = rng.gen_range(5usize..u8::MAX as usize);
-                        let mut salt = vec!{0u8; salt_len};
-                        rng.try_fill_bytes(&mut salt).map_err(StoreError::Rand)?;
-                        fs::write(directory.join(file_name), salt.as_slice()).map_err(StoreError::IO)?;
-                        Ok(salt)
+        let mut args = rest.into_iter();
+        let command = match args.next().as_deref() {
+            Some("list") => Command::List,
+            Some("get") => Command::Get(index(&mut args)?),
+            Some("add") => {
+                let username = args.next().ok_or("add expects a username")?;
+                let (mut kind, mut description) = (SecretKind::Password, String::default());
+                while let Some(arg) = args.next() {
+                    match arg.as_str() {
+                        "--pin" => kind = SecretKind::Pin,
+                        "--passphrase" => kind = SecretKind::Passphrase,
+                        "--description" => description = option_value(&mut args, &arg)?,
+                        _ => return Err(format!("unexpected argument '{}'", arg)),
                     }
                 }
-            };
-
-            match fs::read_dir(&directory) {
-                Ok(read_dir) => {
-                    let files = read_dir.filter_map(|file| {
-                        file.ok().and_then(|dir| {
-                            let file_name = dir.file_name().to_str().filter(|&name| name.ends_with(SALT_EXTENTION)).map(str::to_owned);
-                            let created_date = dir.metadata().and_then(|metadata| metadata.created()).ok();
-                            file_name.and_then(|name| created_date.map(|date| (name, date)))
-                        })
-                    }).collect::<Vec::<(String, time::SystemTime)>>();
-                    let latest_file = files.iter().max_by(|&a, &b| a.1.cmp(&b.1)).map(|(name, _)| name.to_owned());
-                    match latest_file {
-                        Some(name) => {
-                            let contents = fs::read(directory.join(name).to_str().unwrap_or_default()).map_err(StoreError::IO)?;
-                            files.into_iter().for_each(|(name, _)| {
-                                let path = directory.clone().join(name).to_owned();
-                                match fs::remove_file(path) {
-                                    Ok(_) => println!("file deleted"),
-                                    Err(_) => eprintln!("unable to delete file"),
-                                }
-                            });
-                            create_new_salt_file(Some(contents))
-                        },
-                        None => create_new_salt_file(None),
+                Command::Add { username, kind, description }
+            },
+            Some("edit") => {
+                let index = index(&mut args)?;
+                let (mut username, mut kind, mut secret, mut description) = (None, None, false, None);
+                while let Some(arg) = args.next() {
+                    match arg.as_str() {
+                        "--username" => username = Some(option_value(&mut args, &arg)?),
+                        "--description" => description = Some(option_value(&mut args, &arg)?),
+                        "--pin" => kind = Some(SecretKind::Pin),
+                        "--password" => kind = Some(SecretKind::Password),
+                        "--passphrase" => kind = Some(SecretKind::Passphrase),
+                        "--secret" => secret = true,
+                        _ => return Err(format!("unexpected argument '{}'", arg)),
                     }
-                },
-                Err(_) => {
-                    fs::create_dir(directory.clone()).map_err(StoreError::IO)?;
-                    create_new_salt_file(None)
-                },
-            }
+                }
+                Command::Edit { index, username, kind, secret, description }
+            },
+            Some("rm") => Command::Remove(index(&mut args)?),
+            Some("copy") => {
+                let index = index(&mut args)?;
+                let username = match args.next().as_deref() {
+                    None | Some("secret") => false,
+                    Some("username") => true,
+                    Some(arg) => return Err(format!("unexpected argument '{}'", arg)),
+                };
+                Command::Copy { index, username }
+            },
+            Some("help" | "--help" | "-h") | None => Command::Help,
+            Some(command) => return Err(format!("unknown command '{}'", command)),
+        };
+        match args.next() {
+            Some(arg) => Err(format!("unexpected argument '{}'", arg)),
+            None => Ok(Invocation { command, secret_fd }),
         }
     }
 
-    #[derive(Debug)]
-    pub enum StoreError {
-        IO(io::Error),
-        Rand(rand::Error),
-        PadError,
-        UnpadError,
-        InstallationError,
+    fn option_value(args: &mut impl Iterator<Item = String>, option: &str) -> Result<String, String> {
+        args.next().ok_or(format!("{} expects a value", option))
     }
 
-    impl From<StoreError> for String {
-        fn from(error: StoreError) -> Self {
-            match error {
-                StoreError::IO(io_error) => io_error.to_string(),
-                StoreError::PadError => String::from("error while padding"),
-                StoreError::UnpadError => String::from("error while unpadding"),
-                StoreError::InstallationError => String::from("error while installing application"),
-                StoreError::Rand(rand_error) => rand_error.to_string(),
-            }
+    /// Credentials are numbered from 1 in the order `list` prints them.
+    fn index(args: &mut impl Iterator<Item = String>) -> Result<usize, String> {
+        match args.next().map(|arg| arg.parse::<usize>()) {
+            Some(Ok(index)) if index > 0 => Ok(index),
+            _ => Err("expected a credential number as shown by list".to_string()),
         }
     }
 
-    pub async fn save(storage: Arc<Storage>, creds: Vec<(String, Secret, String)>) -> Result<(), StoreError> {
-        let content = creds.into_iter().map(|(username, secret, description)| {
-            format!("{},{},{}", username, secret, description)
-        }).collect::<Vec::<String>>().join("\n");
+    fn execute(invocation: Invocation) -> Result<(), String> {
+        if invocation.command == Command::Help {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        let creating = !core::vault_exists();
+        if creating && !matches!(invocation.command, Command::Add { .. }) {
+            return Err("no vault found, add a credential to create one".to_string());
+        }
 
-        let mut buffer = Vec::new();
-        for chunk in content.as_bytes().chunks(16) {
-            let mut block_array: GenericArray<u8, _> = {
-                let mut padded_array = [0xff; 16];
-                let padded_block = Pkcs7::pad(chunk, 16)?;
-                padded_array.copy_from_slice(padded_block.as_slice());
-                GenericArray::from(padded_array)
-            };
-            storage.cipher.encrypt_block(&mut block_array);
-            buffer.extend_from_slice(block_array.as_slice());
+        let mut master_secret = match invocation.secret_fd {
+            Some(fd) => read_fd(fd)?,
+            None => prompt_master_secret(creating)?,
+        };
+        if master_secret.is_empty() {
+            return Err("master password cannot be empty".to_string());
         }
+        let (storage, mut creds) = block_on(core::unlock(std::mem::take(&mut *master_secret)))?;
 
-        fs::create_dir_all(&storage.directory).map_err(StoreError::IO)?;
-        let mut file = fs::File::create(storage.directory.join(&storage.file_name)).map_err(StoreError::IO)?;
-        file.write_all(buffer.as_slice()).map_err(StoreError::IO)?;
+        match invocation.command {
+            Command::List => for (i, cred) in creds.iter().enumerate() {
+                println!("{}\t{}\t{}\t{}\t{}", i + 1, cred.title, cred.username, cred.secret.kind(), cred.description.replace('\n', " "));
+            },
+            Command::Get(index) => {
+                println!("{}", cred(&creds, index)?.secret.value());
+                touch(storage, &creds, index)?;
+            },
+            Command::Add { username, kind, description } => {
+                let secret = read_secret()?;
+                creds.push(new_cred(username, secret, kind, description)?);
+                let index = creds.len();
+                save(storage, creds)?;
+                eprintln!("Credential {} added", index);
+            },
+            Command::Edit { index, username, kind, secret, description } => {
+                let old = cred(&creds, index)?.clone();
+                let kind = kind.unwrap_or(old.secret.kind());
+                let secret = match secret {
+                    true => read_secret()?,
+                    false => Zeroizing::new(old.secret.value().to_owned()),
+                };
+                creds[index - 1] = core::Credential { id: old.id, title: old.title, folder: old.folder, urls: old.urls, tags: old.tags, fields: old.fields, policy: old.policy, created: old.created, used: old.used, ..new_cred(username.unwrap_or(old.username), secret, kind, description.unwrap_or(old.description))? };
+                save(storage, creds)?;
+                eprintln!("Credential {} updated", index);
+            },
+            Command::Remove(index) => {
+                cred(&creds, index)?;
+                creds.remove(index - 1);
+                save(storage, creds)?;
+                eprintln!("Credential {} removed", index);
+            },
+            Command::Copy { index, username } => {
+                let cred = cred(&creds, index)?;
+                let value = if username { &cred.username } else { cred.secret.value() };
+                touch(storage, &creds, index)?;
+                copy(value, Duration::from_secs(core::Config::load().clipboard_seconds))?;
+            },
+            Command::Help => unreachable!(),
+        }
         Ok(())
     }
 
-    pub async fn fetch(storage: Arc<Storage>) -> Result<Vec<(String, Secret, String)>, StoreError> {
-        let buffer = fs::read(storage.directory.join(&storage.file_name)).map_err(StoreError::IO)?;
-        let mut decrypted_buffer: Vec<u8> = Vec::new();
-        for chunk in buffer.chunks(16) {
-            if chunk.len() < 16 {
-                return Err(StoreError::PadError);
-            }
-            let mut block_array = GenericArray::from_slice(chunk).to_owned();
-            storage.cipher.decrypt_block(&mut block_array);
-            let content = Pkcs7::unpad(&block_array)?;
-            decrypted_buffer.extend_from_slice(content);
-        }
-        let content = String::from_utf8_lossy(decrypted_buffer.as_slice());
-        let content = content.lines().map(|buffer| {
-            let mut iter = buffer.split(',');
-            let username = iter.next().unwrap().to_owned();
-            let secret = Secret::from(iter.next().unwrap());
-            let description = iter.next().unwrap_or_default().to_owned();
-            (username, secret, description)
-        }).collect::<Vec::<(String, Secret, String)>>();
-        Ok(content)
-    }
-
-    struct Pkcs7;
-
-    impl Pkcs7 {
-        fn pad(block: &[u8], len: usize) -> Result<Vec<u8>, StoreError> {
-            if block.len() > 255 || len < block.len() {
-                Err(StoreError::PadError)
-            }
-            else {
-                let n = len - block.len();
-                let padded_block = block.iter().chain(std::iter::repeat(&(n as u8)).take(n)).map(u8::to_owned).collect::<Vec::<u8>>();
-                Ok(padded_block)
-            }
-        }
+    fn cred(creds: &[core::Credential], index: usize) -> Result<&core::Credential, String> {
+        creds.get(index - 1).ok_or(format!("no credential {}, the vault has {}", index, creds.len()))
+    }
 
-        fn unpad(block: &[u8]) -> Result<&[u8], StoreError> {
-            let n = block.last().ok_or(StoreError::UnpadError)?;
-            if block.len() > u8::MAX as usize || *n == 0 || *n as usize >= block.len() {
-                Ok(block)
-            }
-            else {
-                let s = block.len() - *n as usize;
-                if block[s..].iter().any(|&v| v != *n) {
-                    Ok(block)
-                }
-                else {
-                    Ok(&block[..s])
-                }
-            }
+    /// Applies the same checks as the insert form of the GUI.
+    fn new_cred(username: String, mut secret: Zeroizing<String>, kind: SecretKind, description: String) -> Result<core::Credential, String> {
+        if username.is_empty() {
+            return Err("username cannot be empty".to_string());
+        }
+        let secret = core::Secret::new(kind, std::mem::take(&mut *secret));
+        if !secret.is_valid() {
+            return Err(match kind {
+                SecretKind::Pin => "pin must be a number without leading zeros",
+                _ => "secret cannot be empty",
+            }.to_string());
         }
+        let now = core::Credential::now();
+        Ok(core::Credential { created: now, modified: now, ..core::Credential::new(username, secret, description) })
     }
 
-    #[derive(Clone, Debug)]
-    pub enum StoreMessage {
-        Fetched(Vec<(String, Secret, String)>),
-        Added,
-        Deleted,
-        Updated,
-        Invalid,
-    }
-    
-    #[test]
-    fn unpad_empty_block() {
-        let arr: &[u8] = &[];
-        let res = Pkcs7::unpad(arr);
-        assert!(res.is_err());
+    fn save(storage: Arc<core::Storage>, creds: Vec<core::Credential>) -> Result<(), String> {
+        Ok(block_on(core::save(storage, creds))?)
     }
 
-    #[test]
-    fn unpad_block_with_length_greater_than_255() {
-        let arr = [2u8; 500];
-        let res = Pkcs7::unpad(&arr[..]);
-        assert!(res.is_ok_and(|unpadded_arr| unpadded_arr == arr));
+    /// Records that credential `index` was used, as the GUI does when copying or revealing it. The
+    /// vault itself is left alone.
+    fn touch(storage: Arc<core::Storage>, creds: &[core::Credential], index: usize) -> Result<(), String> {
+        Ok(block_on(core::touch(storage, creds[index - 1].id, core::Credential::now()))?)
     }
 
-    #[test]
-    fn unpad_block_with_last_byte_0() {
-        let arr = (0..100).rev().collect::<Vec<u8>>();
-        let res = Pkcs7::unpad(arr.as_slice());
-        assert!(res.is_ok_and(|unpadded_arr| unpadded_arr == arr));
+    fn prompt_master_secret(creating: bool) -> Result<Zeroizing<String>, String> {
+        let secret = Zeroizing::new(rpassword::prompt_password("Master password: ").map_err(|e| e.to_string())?);
+        if creating && Zeroizing::new(rpassword::prompt_password("Confirm master password: ").map_err(|e| e.to_string())?) != secret {
+            return Err("master passwords do not match".to_string());
+        }
+        Ok(secret)
     }
 
-    #[test]
-    fn unpad_block_with_last_byte_greater_than_length_of_array() {
-        let arr = [50u8, 51, 52];
-        let res = Pkcs7::unpad(&arr[..]);
-        assert!(res.is_ok_and(|unpadded_arr| unpadded_arr == &arr[..]));
+    /// Reads the first line of an inherited file descriptor, e.g. `--password-fd 3 3<secret-file`.
+    #[cfg(unix)]
+    fn read_fd(fd: i32) -> Result<Zeroizing<String>, String> {
+        let file = std::fs::File::open(format!("/dev/fd/{}", fd)).map_err(|e| format!("cannot read file descriptor {}: {}", fd, e))?;
+        read_line(io::BufReader::new(file)).map_err(|e| format!("cannot read file descriptor {}: {}", fd, e))
     }
 
-    #[test]
-    fn unpad_block_with_bytes_ne_to_unpad_length() {
-        let arr = [9, 8, 7, 6, 5, 4, 3, 10, 10, 10, 10, 10, 10, 3, 10, 10, 10];
-        let res = Pkcs7::unpad(&arr[..]);
-        assert!(res.is_ok_and(|unpadded_arr| unpadded_arr == &arr[..]));
+    #[cfg(not(unix))]
+    fn read_fd(_fd: i32) -> Result<Zeroizing<String>, String> {
+        Err("--password-fd is only supported on unix".to_string())
     }
 
-    #[test]
-    fn unpad_valid_block() {
-        let arr = [42, 35, 23, 187, 34, 69, 248, 21, 231, 126, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12];
-        let res = Pkcs7::unpad(&arr[..]);
-        let expected = [42, 35, 23, 187, 34, 69, 248, 21, 231, 126];
-        assert!(res.is_ok_and(|unpadded_arr| unpadded_arr == expected));
+    /// Secrets never appear on the command line, where other users could read them from the process list.
+    fn read_secret() -> Result<Zeroizing<String>, String> {
+        if io::stdin().is_terminal() {
+            return rpassword::prompt_password("Secret: ").map(Zeroizing::new).map_err(|e| e.to_string());
+        }
+        read_line(io::stdin().lock()).map_err(|e| e.to_string())
     }
 
-    #[test]
-    fn salt_creation() {
-        let storage = Storage::new_from_secret("my_secret");
-        assert!(storage.is_ok());
+    /// The line is trimmed in place, so no copy of it is left behind unwiped. A reader growing the
+    /// line may still leave parts of it in freed memory, so it is read into room for a long one.
+    fn read_line(mut reader: impl BufRead) -> io::Result<Zeroizing<String>> {
+        let mut line = Zeroizing::new(String::with_capacity(1024));
+        reader.read_line(&mut line)?;
+        let len = line.trim_end_matches(['\r', '\n']).len();
+        line.truncate(len);
+        Ok(line)
     }
 
-    #[test]
-    fn salt_update() {
-        const SALT_EXTENTION: &str = "salt";
-        let directory = {
-            let home_dir = home::home_dir().unwrap_or_default();
-            if cfg!(target_os = "windows") {
-                home_dir.join("AppData").join(TITLE.to_lowercase())
-            }
-            else if cfg!(target_os = "macos") {
-                home_dir.join("Library").join("Application Support").join(TITLE.to_lowercase())
-            }
-            else {
-                home_dir.join(".config").join(TITLE.to_lowercase())
-            }
-        };
-
-        if let Err(_) = fs::create_dir_all(&directory) {
-            panic!("Failed to create directory");
-        }
-
-        let mut rng = rand::thread_rng();
-        let mut create_salt_file = || {
-            let file_name= {
-                let length = rng.gen_range(10 - SALT_EXTENTION.len()..=30 - SALT_EXTENTION.len());
-                let random = (0..length).map(|_| {
-                    let choice = rng.gen_range(0u8..3);
-                    match choice {
-                        0 => rng.gen_range('a'..='z'),
-                        1 => rng.gen_range('A'..='Z'),
-                        2 => rng.gen_range('0'..='9'),
-                        _ => unreachable!(),
-                    }
-                }).collect::<String>();
-                format!("{}{}", random, SALT_EXTENTION)
-            };
-            let salt_len = rng.gen_range(5usize..u8::MAX as usize);
-            let mut salt = vec!{0u8; salt_len};
-            if let Err(_) = rng.try_fill_bytes(&mut salt) {
-                panic!("error filling random bytes");
-            }
-            if let Err(_) = fs::write(directory.join(file_name), salt.as_slice()) {
-                panic!("error writing to file");
-            }
-            salt
-        };
-
-        create_salt_file();
-        std::thread::sleep(time::Duration::from_secs(1));
-        create_salt_file();
-        std::thread::sleep(time::Duration::from_secs(1));
-        create_salt_file();
-        std::thread::sleep(time::Duration::from_secs(1));
-        let content = create_salt_file();
-        std::thread::sleep(time::Duration::from_secs(1));
-
-        let storage = Storage::new_from_secret("my_secret");
-        assert!(storage.is_ok());
-
-        if let Ok(read_dir) = fs::read_dir(&directory) {
-            let files = read_dir.filter_map(|file| file.ok().and_then(|dir| dir.file_name().to_str().filter(|&name| name.ends_with(SALT_EXTENTION)).map(str::to_owned))).collect::<Vec::<String>>();
-            assert_eq!(files.len(), 1);
-            let file_content = fs::read(directory.join(files.get(0).unwrap())).expect("error reading file");
-            assert_eq!(file_content, content);
+    /// X11 and Wayland clipboards are served by the process that set them, so there `copy` stays alive
+    /// until the value is due to be cleared. Elsewhere it waits and clears the clipboard itself, unless
+    /// something else was copied meanwhile.
+    fn copy(value: &str, hold: Duration) -> Result<(), String> {
+        let mut clipboard = arboard::Clipboard::new().map_err(|e| e.to_string())?;
+        eprintln!("Copied to clipboard, cleared after {} seconds", hold.as_secs());
+        #[cfg(target_os = "linux")]
+        {
+            use arboard::SetExtLinux;
+            let now = std::time::Instant::now();
+            clipboard.set().wait_until(now.checked_add(hold).unwrap_or(now)).text(value).map_err(|e| e.to_string())
         }
-        else {
-            panic!("error reading directory");
+        #[cfg(not(target_os = "linux"))]
+        {
+            clipboard.set_text(value).map_err(|e| e.to_string())?;
+            std::thread::sleep(hold);
+            if clipboard.get_text().map(Zeroizing::new).is_ok_and(|content| content.as_str() == value) {
+                clipboard.clear().map_err(|e| e.to_string())?;
+            }
+            Ok(())
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parse_arguments() {
+        let args = |line: &str| line.split(' ').map(str::to_owned).collect::<Vec<String>>();
+        assert_eq!(parse(args("--password-fd 3 get 2")), Ok(Invocation { command: Command::Get(2), secret_fd: Some(3) }));
+        assert_eq!(parse(args("add alice --pin --description work")).map(|i| i.command), Ok(Command::Add { username: "alice".to_string(), kind: SecretKind::Pin, description: "work".to_string() }));
+        assert_eq!(parse(args("edit 1 --secret --password-fd 4")).map(|i| (i.command, i.secret_fd)), Ok((Command::Edit { index: 1, username: None, kind: None, secret: true, description: None }, Some(4))));
+        assert_eq!(parse(args("copy 3 username")).map(|i| i.command), Ok(Command::Copy { index: 3, username: true }));
+        assert!(parse(args("get 0")).is_err());
+        assert!(parse(args("rm 1 2")).is_err());
+        assert!(parse(args("add alice --description")).is_err());
+        assert!(parse(args("move 1")).is_err());
+    }
+}
+